@@ -0,0 +1,2 @@
+ALTER TABLE checks
+    ADD CONSTRAINT checks_ledger_id_key UNIQUE (ledger_id);
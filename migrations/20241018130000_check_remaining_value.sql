@@ -0,0 +1,30 @@
+ALTER TABLE checks
+    ADD COLUMN remaining bigint NOT NULL DEFAULT 0 CHECK (remaining >= 0);
+
+-- Checks created before remaining values were tracked have no row in checks
+-- (or only the row recording that they were cashed). Give every CreateCheck
+-- ledger entry a row and backfill what's left on it from the check's value
+-- less whatever CashCheck transactions have already paid out of it.
+INSERT INTO checks (ledger_id)
+SELECT ledger.id
+FROM ledger
+JOIN transactions ON transactions.id = ledger.transaction_id
+-- Transaction::CreateCheck
+WHERE get_byte(transactions.data, 0) = 1
+ON CONFLICT (ledger_id) DO NOTHING;
+
+UPDATE checks
+SET remaining = GREATEST(ledger.value - COALESCE((
+    SELECT SUM(cashed.value)
+    FROM ledger AS cashed
+    JOIN transactions ON transactions.id = cashed.transaction_id
+    -- Transaction::CashCheck followed by the check's little endian transaction id
+    WHERE get_byte(transactions.data, 0) = 2
+    AND cashed.payor_id = ledger.recipient_id
+    AND (
+        SELECT SUM(get_byte(transactions.data, i)::bigint << (8 * (i - 1)))
+        FROM generate_series(1, 8) AS i
+    ) = ledger.transaction_id
+), 0), 0)
+FROM ledger
+WHERE ledger.id = checks.ledger_id;
@@ -0,0 +1,2 @@
+ALTER TABLE ledger
+    ADD COLUMN memo text CHECK (octet_length(memo) <= 140);
@@ -0,0 +1,5 @@
+CREATE TABLE locks(
+    id bigserial PRIMARY KEY,
+    ledger_id bigint NOT NULL UNIQUE REFERENCES ledger(id) ON DELETE RESTRICT,
+    settled boolean NOT NULL DEFAULT FALSE
+);
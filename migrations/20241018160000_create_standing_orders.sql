@@ -0,0 +1,19 @@
+CREATE TABLE standing_orders(
+    id bigserial PRIMARY KEY,
+    transaction_id bigint NOT NULL UNIQUE REFERENCES transactions(id) ON DELETE RESTRICT,
+    payor_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    recipient_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    currency currency NOT NULL,
+    value bigint NOT NULL CHECK (value > 0),
+    interval bigint NOT NULL CHECK (interval > 0),
+    remaining_occurrences bigint NOT NULL,
+    next_block_height bigint NOT NULL,
+    cancelled boolean NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE standing_order_failures(
+    id bigserial PRIMARY KEY,
+    standing_order_id bigint NOT NULL REFERENCES standing_orders(id) ON DELETE RESTRICT,
+    block_height int NOT NULL,
+    reason text NOT NULL
+);
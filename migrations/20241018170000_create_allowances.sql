@@ -0,0 +1,68 @@
+CREATE TABLE allowances(
+    owner_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    spender_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    currency currency NOT NULL,
+    value bigint NOT NULL DEFAULT 0 CHECK (value >= 0),
+    PRIMARY KEY (owner_id, spender_id, currency)
+);
+
+ALTER TABLE ledger
+    ADD COLUMN spender_id int REFERENCES accounts(id) ON DELETE RESTRICT;
+
+CREATE FUNCTION allowance(owner_id int, spender_id int, currency currency)
+    RETURNS bigint
+    AS $$
+    SELECT
+        COALESCE((
+            SELECT
+                value
+            FROM allowances
+            WHERE
+                allowances.owner_id = $1
+                AND allowances.spender_id = $2
+                AND allowances.currency = $3), 0)
+$$
+LANGUAGE sql;
+
+CREATE OR REPLACE FUNCTION validate_entry()
+    RETURNS TRIGGER
+    AS $$
+BEGIN
+    IF NEW.value <= 0 THEN
+        RAISE EXCEPTION 'Ledger entries must have a positive value';
+    END IF;
+    IF balance(NEW.payor_id, NEW.currency) < NEW.value AND NEW.payor_id != system_address() THEN
+        RAISE EXCEPTION 'Payor has insufficient funds';
+    END IF;
+    IF NEW.spender_id IS NOT NULL AND allowance(NEW.payor_id, NEW.spender_id, NEW.currency) < NEW.value THEN
+        RAISE EXCEPTION 'Spender has insufficient allowance';
+    END IF;
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION update_account_balances()
+    RETURNS TRIGGER
+    AS $$
+BEGIN
+    INSERT INTO balances(account_id, currency, value)
+        VALUES(NEW.recipient_id, NEW.currency, NEW.value)
+    ON CONFLICT(account_id, currency)
+        DO UPDATE SET
+            value = balances.value + NEW.value;
+    INSERT INTO balances(account_id, currency, value)
+        VALUES(NEW.payor_id, NEW.currency, - NEW.value)
+    ON CONFLICT(account_id, currency)
+        DO UPDATE SET
+            value = balances.value - NEW.value;
+    IF NEW.spender_id IS NOT NULL THEN
+        UPDATE allowances SET value = value - NEW.value
+        WHERE owner_id = NEW.payor_id
+            AND spender_id = NEW.spender_id
+            AND currency = NEW.currency;
+    END IF;
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,2 @@
+ALTER TABLE accounts
+    ADD COLUMN key_address bytea UNIQUE;
@@ -0,0 +1,30 @@
+CREATE TABLE recovery_policies(
+    account_id int PRIMARY KEY REFERENCES accounts(id) ON DELETE RESTRICT,
+    threshold int NOT NULL CHECK (threshold > 0),
+    delay bigint NOT NULL CHECK (delay >= 0)
+);
+
+CREATE TABLE recovery_guardians(
+    account_id int NOT NULL REFERENCES recovery_policies(account_id) ON DELETE CASCADE,
+    guardian_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    PRIMARY KEY (account_id, guardian_id)
+);
+
+CREATE TABLE recoveries(
+    id bigserial PRIMARY KEY,
+    transaction_id bigint NOT NULL UNIQUE REFERENCES transactions(id) ON DELETE RESTRICT,
+    account_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    key_address bytea NOT NULL,
+    executable_at bigint NOT NULL,
+    cancelled boolean NOT NULL DEFAULT FALSE,
+    finalized boolean NOT NULL DEFAULT FALSE
+);
+
+CREATE UNIQUE INDEX recoveries_pending_account_id ON recoveries(account_id)
+    WHERE NOT cancelled AND NOT finalized;
+
+CREATE TABLE recovery_approvals(
+    recovery_id bigint NOT NULL REFERENCES recoveries(id) ON DELETE RESTRICT,
+    guardian_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    PRIMARY KEY (recovery_id, guardian_id)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE fees(
+    transaction_type text NOT NULL,
+    currency currency NOT NULL,
+    flat bigint NOT NULL DEFAULT 0 CHECK (flat >= 0),
+    basis_points int NOT NULL DEFAULT 0 CHECK (basis_points >= 0 AND basis_points <= 10000),
+    PRIMARY KEY (transaction_type, currency)
+);
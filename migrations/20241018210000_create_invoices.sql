@@ -0,0 +1,10 @@
+CREATE TABLE invoices(
+    id bigserial PRIMARY KEY,
+    transaction_id bigint NOT NULL UNIQUE REFERENCES transactions(id) ON DELETE RESTRICT,
+    payee_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    currency currency NOT NULL,
+    value bigint NOT NULL CHECK (value > 0),
+    memo text CHECK (octet_length(memo) <= 140),
+    expires_at bigint,
+    ledger_id bigint UNIQUE REFERENCES ledger(id) ON DELETE RESTRICT
+);
@@ -0,0 +1,15 @@
+CREATE TABLE session_keys(
+    id bigserial PRIMARY KEY,
+    transaction_id bigint NOT NULL REFERENCES transactions(id) ON DELETE RESTRICT,
+    account_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    key_address bytea NOT NULL UNIQUE,
+    currency currency NOT NULL,
+    remaining bigint NOT NULL CHECK (remaining >= 0),
+    expires_at bigint NOT NULL
+);
+
+CREATE TABLE session_key_recipients(
+    session_key_id bigint NOT NULL REFERENCES session_keys(id) ON DELETE CASCADE,
+    recipient_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    PRIMARY KEY (session_key_id, recipient_id)
+);
@@ -0,0 +1,5 @@
+CREATE TABLE names(
+    name text PRIMARY KEY CHECK (name ~ '^[a-z0-9_-]{1,32}$'),
+    account_id int NOT NULL UNIQUE REFERENCES accounts(id) ON DELETE RESTRICT,
+    transaction_id bigint NOT NULL REFERENCES transactions(id) ON DELETE RESTRICT
+);
@@ -0,0 +1,13 @@
+-- Transactions stored before this migration have no known block height or
+-- time so the columns are added without defaults to leave them NULL
+ALTER TABLE transactions
+    ADD COLUMN block_height int,
+    ADD COLUMN created_at timestamptz;
+
+ALTER TABLE transactions
+    ALTER COLUMN block_height SET DEFAULT current_block(),
+    ALTER COLUMN created_at SET DEFAULT now();
+
+CREATE INDEX ledger_payor_id ON ledger(payor_id);
+
+CREATE INDEX ledger_recipient_id ON ledger(recipient_id);
@@ -0,0 +1,142 @@
+-- Currencies are rows in the currencies table rather than values of a
+-- Postgres ENUM so new ones can be added without a migration.
+DROP FUNCTION currency_decimal_multiplier(currency);
+
+DROP FUNCTION satoshis_to_currency(currency, bigint);
+
+DROP FUNCTION currency_to_satoshis(currency, bigint);
+
+DROP FUNCTION balance(bigint, currency);
+
+DROP FUNCTION allowance(int, int, currency);
+
+ALTER TABLE currencies RENAME COLUMN currency TO code;
+
+ALTER TABLE currencies
+    ALTER COLUMN code TYPE text USING code::text,
+    ALTER COLUMN decimals SET NOT NULL,
+    ADD COLUMN enabled boolean NOT NULL DEFAULT TRUE,
+    ADD CONSTRAINT currencies_code_format CHECK (code ~ '^[a-z0-9]{1,16}$');
+
+ALTER TABLE balances
+    ALTER COLUMN currency TYPE text USING currency::text,
+    ADD FOREIGN KEY (currency) REFERENCES currencies(code) ON DELETE RESTRICT;
+
+ALTER TABLE exchange_rates
+    ALTER COLUMN currency TYPE text USING currency::text,
+    ADD FOREIGN KEY (currency) REFERENCES currencies(code) ON DELETE RESTRICT;
+
+ALTER TABLE ledger
+    ALTER COLUMN currency TYPE text USING currency::text,
+    ADD FOREIGN KEY (currency) REFERENCES currencies(code) ON DELETE RESTRICT;
+
+ALTER TABLE standing_orders
+    ALTER COLUMN currency TYPE text USING currency::text,
+    ADD FOREIGN KEY (currency) REFERENCES currencies(code) ON DELETE RESTRICT;
+
+ALTER TABLE allowances
+    ALTER COLUMN currency TYPE text USING currency::text,
+    ADD FOREIGN KEY (currency) REFERENCES currencies(code) ON DELETE RESTRICT;
+
+ALTER TABLE fees
+    ALTER COLUMN currency TYPE text USING currency::text,
+    ADD FOREIGN KEY (currency) REFERENCES currencies(code) ON DELETE RESTRICT;
+
+ALTER TABLE invoices
+    ALTER COLUMN currency TYPE text USING currency::text,
+    ADD FOREIGN KEY (currency) REFERENCES currencies(code) ON DELETE RESTRICT;
+
+ALTER TABLE session_keys
+    ALTER COLUMN currency TYPE text USING currency::text,
+    ADD FOREIGN KEY (currency) REFERENCES currencies(code) ON DELETE RESTRICT;
+
+DROP TYPE currency;
+
+CREATE FUNCTION currency_decimal_multiplier(currency text)
+    RETURNS int
+    AS $$
+    SELECT
+(10 ^(
+                SELECT
+                    decimals
+                FROM currencies
+                WHERE
+                    currencies.code = $1))
+$$
+LANGUAGE sql;
+
+CREATE FUNCTION satoshis_to_currency(currency text, value bigint)
+    RETURNS bigint
+    AS $$
+    SELECT
+((value * $2) +(50000000)) / 100000000
+    FROM
+        exchange_rates
+    WHERE
+        currency = $1
+        AND block_height = current_block()
+$$
+LANGUAGE sql;
+
+CREATE FUNCTION currency_to_satoshis(currency text, value bigint)
+    RETURNS bigint
+    AS $$
+    SELECT
+((($2 * 100000000) - 50000000) / exchange_rates.value)
+    FROM
+        exchange_rates
+    WHERE
+        currency = $1
+        AND block_height = current_block();
+$$
+LANGUAGE sql;
+
+CREATE FUNCTION balance(account_id bigint, currency text)
+    RETURNS int
+    AS $$
+    SELECT
+        COALESCE((
+            SELECT
+                value
+            FROM balances
+            WHERE
+                balances.account_id = $1
+                AND balances.currency = $2), 0)
+$$
+LANGUAGE sql;
+
+CREATE FUNCTION allowance(owner_id int, spender_id int, currency text)
+    RETURNS bigint
+    AS $$
+    SELECT
+        COALESCE((
+            SELECT
+                value
+            FROM allowances
+            WHERE
+                allowances.owner_id = $1
+                AND allowances.spender_id = $2
+                AND allowances.currency = $3), 0)
+$$
+LANGUAGE sql;
+
+CREATE OR REPLACE FUNCTION validate_entry()
+    RETURNS TRIGGER
+    AS $$
+BEGIN
+    IF NEW.value <= 0 THEN
+        RAISE EXCEPTION 'Ledger entries must have a positive value';
+    END IF;
+    IF NOT (SELECT enabled FROM currencies WHERE code = NEW.currency) THEN
+        RAISE EXCEPTION 'Currency % is not enabled', NEW.currency;
+    END IF;
+    IF balance(NEW.payor_id, NEW.currency) < NEW.value AND NEW.payor_id != system_address() THEN
+        RAISE EXCEPTION 'Payor has insufficient funds';
+    END IF;
+    IF NEW.spender_id IS NOT NULL AND allowance(NEW.payor_id, NEW.spender_id, NEW.currency) < NEW.value THEN
+        RAISE EXCEPTION 'Spender has insufficient allowance';
+    END IF;
+    RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,7 @@
+CREATE TABLE sponsorships(
+    transaction_id bigint PRIMARY KEY REFERENCES transactions(id) ON DELETE RESTRICT,
+    sponsor_id int NOT NULL REFERENCES accounts(id) ON DELETE RESTRICT,
+    currency text NOT NULL REFERENCES currencies(code),
+    miner_fee bigint NOT NULL CHECK (miner_fee > 0),
+    signature bytea NOT NULL CHECK (octet_length(signature) = 65)
+);
@@ -0,0 +1,4 @@
+-- The protocol version each transaction was stored with. Transactions stored
+-- before it was recorded are in the legacy wire format.
+ALTER TABLE transactions
+    ADD COLUMN version smallint NOT NULL DEFAULT 0;
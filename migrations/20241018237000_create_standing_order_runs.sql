@@ -0,0 +1,10 @@
+-- Standing order payments are recorded under the transaction that created the
+-- standing order so each run links its ledger entry back to the order along
+-- with when it ran
+CREATE TABLE standing_order_runs(
+    id bigserial PRIMARY KEY,
+    standing_order_id bigint NOT NULL REFERENCES standing_orders(id) ON DELETE RESTRICT,
+    ledger_id bigint NOT NULL UNIQUE REFERENCES ledger(id) ON DELETE RESTRICT,
+    block_height int NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
@@ -1,4 +1,4 @@
-use crate::{BorshDeserialize, BorshSerialize};
+use crate::{transaction::MultiSigPolicy, BorshDeserialize, BorshSerialize};
 use bitcoin::ScriptBuf;
 use k256::ecdsa::VerifyingKey;
 use sha2::{Digest, Sha256};
@@ -8,6 +8,12 @@ use sha2::{Digest, Sha256};
 #[sqlx(transparent)]
 pub struct Address(pub [u8; 17]);
 
+impl serde::Serialize for Address {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.0))
+    }
+}
+
 impl From<[u8; 33]> for Address {
     fn from(array: [u8; 33]) -> Self {
         // println!("{}", hex::encode(array));
@@ -17,6 +23,13 @@ impl From<[u8; 33]> for Address {
     }
 }
 
+impl From<&MultiSigPolicy> for Address {
+    fn from(policy: &MultiSigPolicy) -> Self {
+        let hash = Sha256::digest(borsh::to_vec(policy).unwrap());
+        Self(hash[15..].try_into().unwrap())
+    }
+}
+
 impl From<bitcoin::PublicKey> for Address {
     fn from(public_key: bitcoin::PublicKey) -> Self {
         public_key.inner.serialize().into()
@@ -0,0 +1,38 @@
+use dotenv::dotenv;
+use sqlx::postgres::PgPoolOptions;
+use stable::{
+    statement::{parse_date, Format},
+    transaction::Currency,
+    Address,
+};
+use std::{env, str::FromStr};
+
+// Usage: statement <address> <currency> <from> <to> [csv|ofx|json]
+// Dates are in YYYY-MM-DD form and `to` is exclusive.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    dotenv().ok();
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 5 {
+        eprintln!(
+            "Usage: {} <address> <currency> <from> <to> [csv|ofx|json]",
+            args[0]
+        );
+        std::process::exit(1);
+    }
+    let address = Address(hex::decode(&args[1])?.as_slice().try_into()?);
+    let currency = Currency::from_str(&args[2]).map_err(|_| "Invalid currency")?;
+    let format = Format::from_str(args.get(5).map(String::as_str).unwrap_or("csv"))?;
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new().connect(&database_url).await?;
+    let statement = stable::db::get_statement(
+        &pool,
+        &address,
+        &currency,
+        parse_date(&args[3])?,
+        parse_date(&args[4])?,
+    )
+    .await?;
+    print!("{}", statement.render(format)?);
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use self::super::rpc;
 use crate::address::script_buf_to_address;
 use crate::db::Utxo;
-use crate::{db, exchange_rates, Address, AppState, Currency};
+use crate::{db, error::Result, exchange_rates, transaction::Currency, Address, AppState};
 use bitcoin::Transaction;
+use log::error;
 use std::collections::HashMap;
 use tokio::time;
 
@@ -33,6 +34,15 @@ pub async fn poll(app_state: &AppState) {
             .expect("Time went backwards");
         println!("{:?}", since_the_epoch);
         let block = rpc::get_block(new_best_block_hash).await;
+        // The block is retried on the next poll if its exchange rates can't be
+        // fetched
+        let exchange_rates = match get_exchange_rates(app_state).await {
+            Ok(exchange_rates) => exchange_rates,
+            Err(err) => {
+                error!("Failed to get exchange rates: {}", err);
+                return;
+            }
+        };
         let deposit_utxos: Vec<(db::Utxo, Address)> = txdata_to_utxos(block.txdata.clone());
 
         for (_, address) in &deposit_utxos {
@@ -47,14 +57,38 @@ pub async fn poll(app_state: &AppState) {
         db::insert_bitcoin_block(
             &app_state.pool.lock().await.clone(),
             block.clone(),
-            HashMap::from([(Currency::Usd, exchange_rates::bitcoin().await.unwrap())]),
+            exchange_rates,
             deposit_utxos,
         )
         .await
         .unwrap();
+        let addresses =
+            match db::execute_standing_orders(&app_state.pool.lock().await.clone()).await {
+                Ok(addresses) => addresses,
+                Err(err) => {
+                    error!("Failed to execute standing orders: {}", err);
+                    return;
+                }
+            };
+        for address in addresses {
+            app_state
+                .update_channel
+                .lock()
+                .await
+                .0
+                .send(address)
+                .unwrap();
+        }
     }
 }
 
+async fn get_exchange_rates(app_state: &AppState) -> Result<HashMap<Currency, f64>> {
+    exchange_rates::bitcoin(
+        &db::get_enabled_currencies(&app_state.pool.lock().await.clone()).await?,
+    )
+    .await
+}
+
 fn txdata_to_utxos(txdata: Vec<Transaction>) -> Vec<(db::Utxo, Address)> {
     txdata
         .clone()
@@ -1,3 +1,4 @@
+use crate::{constants::MIN_FEE_RATE, error::Result};
 use bitcoin::{consensus::Decodable, Network};
 use log::info;
 use rust_decimal::Decimal;
@@ -134,6 +135,37 @@ pub async fn get_block(block_hash: [u8; 32]) -> bitcoin::Block {
     bitcoin::Block::consensus_decode(&mut raw).unwrap()
 }
 
+// Fee rate in satoshis per vbyte for a transaction to confirm within
+// `target` blocks. Falls back to the minimum relay fee rate when bitcoind
+// doesn't have enough data to estimate one.
+pub async fn estimate_fee_rate(target: i64) -> Result<i64> {
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(env::var("BITCOIND_URL").unwrap())
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            reqwest::header::HeaderValue::from_static("text/plain"),
+        )
+        .json(&json!({
+                    "jsonrpc": "1.0",
+                    "method": "estimatesmartfee",
+                    "params": [target]
+        }))
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    Ok(resp
+        .get("result")
+        .and_then(|result| result.get("feerate"))
+        .and_then(Value::as_f64)
+        // BTC per kvB
+        .map(|fee_rate| (fee_rate * 100_000.0).ceil() as i64)
+        .unwrap_or(MIN_FEE_RATE)
+        .max(MIN_FEE_RATE))
+}
+
 #[cfg(test)]
 mod tests {
 
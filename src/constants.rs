@@ -8,6 +8,23 @@ pub enum Env {
     Development,
 }
 pub const SYSTEM_ADDRESS: Address = Address([0; 17]);
+pub const ESCROW_ADDRESS: Address = Address([1; 17]);
+pub const MAX_MEMO_LENGTH: usize = 140;
+pub const MAX_NAME_LENGTH: usize = 32;
+pub const MAX_CURRENCY_CODE_LENGTH: usize = 16;
+pub const MAX_BATCH_SIZE: usize = 100;
+pub const TRANSFER_FEE: &str = "transfer";
+pub const CREATE_CHECK_FEE: &str = "create_check";
+pub const SIGNING_DOMAIN_TAG: &str = "stable:transaction";
+pub const SPONSOR_DOMAIN_TAG: &str = "stable:sponsor";
+// Sponsored withdrawals must cover the estimated miner fee of a withdrawal
+// this size at the fee rate needed to confirm within the target
+pub const WITHDRAWAL_VSIZE: i64 = 141;
+pub const WITHDRAWAL_CONFIRMATION_TARGET: i64 = 6;
+pub const MIN_FEE_RATE: i64 = 1;
+// Version 2 added valid_until to the signed transaction envelope and version 3
+// encodes currencies as their codes instead of an enum tag
+pub const PROTOCOL_VERSION: u8 = 3;
 lazy_static! {
     pub static ref ENV: Env = if env::var("ENV").unwrap_or("".to_string()) == "production" {
         Env::Production
@@ -20,7 +37,15 @@ lazy_static! {
         .unwrap_or(80);
     pub static ref PUBLIC_KEY: PublicKey =
         PublicKey::from_private_key(&Secp256k1::new(), &*PRIVATE_KEY);
-    pub static ref NODE_ADDRESS: Address = (*PUBLIC_KEY).into();
+    // Network fees are paid to the node's address. Nodes without a key can't
+    // charge fees.
+    pub static ref NODE_ADDRESS: Option<Address> = env::var("PRIVATE_KEY").ok().map(|wif| {
+        PublicKey::from_private_key(
+            &Secp256k1::new(),
+            &PrivateKey::from_wif(&wif).expect("Failed to parse PRIVATE_KEY"),
+        )
+        .into()
+    });
     static ref PRIVATE_KEY: PrivateKey =
         PrivateKey::from_wif(&env::var("PRIVATE_KEY").expect("PRIVATE_KEY must be set")).unwrap();
     pub static ref PUBLIC_IP: IpAddr = env::var("PUBLIC_IP")
@@ -41,5 +66,9 @@ lazy_static! {
             .collect::<Vec<String>>()
             .clone()))
         .unwrap_or(vec![]);
+    // Set to accept transactions signed without a signing domain while clients upgrade
+    pub static ref ACCEPT_LEGACY_SIGNATURES: bool = env::var("ACCEPT_LEGACY_SIGNATURES")
+        .map(|accept| accept == "true")
+        .unwrap_or(false);
     pub static ref COIN_MARKET_CAP_KEY: String = env::var("COIN_MARKET_CAP_KEY").unwrap();
 }
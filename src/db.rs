@@ -1,15 +1,26 @@
 use crate::{
     address::Address,
     bitcoin::multi_sig,
-    constants::{PUBLIC_IP, PUBLIC_KEY, SYSTEM_ADDRESS},
+    constants::{
+        CREATE_CHECK_FEE, ESCROW_ADDRESS, MAX_BATCH_SIZE, MAX_MEMO_LENGTH, MAX_NAME_LENGTH,
+        NODE_ADDRESS, PROTOCOL_VERSION, PUBLIC_IP, PUBLIC_KEY, SYSTEM_ADDRESS, TRANSFER_FEE,
+        WITHDRAWAL_CONFIRMATION_TARGET, WITHDRAWAL_VSIZE,
+    },
     error::{Error, Result},
+    invoice::Invoice,
+    legacy,
+    statement::{Statement, StatementEntry},
     transaction::{self, Currency},
-    SignedTransaction, Transaction,
+    SignedTransaction, Sponsorship, Transaction,
 };
 use bitcoin::{BlockHash, Network};
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::NaiveDate;
 use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::PgConnection;
-use sqlx::{query, query_as, Executor, PgPool, Postgres, Row};
+use sqlx::{postgres::PgRow, query, query_as, Executor, PgPool, Postgres, Row};
 use std::{collections::HashMap, net::IpAddr, str::FromStr};
 
 #[cfg(test)]
@@ -30,198 +41,1997 @@ where
         .await?;
     Ok(())
 }
-pub async fn run_transaction(pool: PgPool, transaction: SignedTransaction) -> Result<i64> {
+pub async fn run_transaction(
+    pool: PgPool,
+    transaction: SignedTransaction,
+    sponsorship: Option<Sponsorship>,
+) -> Result<i64> {
+    transaction.verify().await?;
+    // The fee rate is estimated before any rows are locked
+    let sponsorship = match sponsorship {
+        Some(sponsorship) => Some((
+            sponsorship,
+            crate::bitcoin::rpc::estimate_fee_rate(WITHDRAWAL_CONFIRMATION_TARGET).await?,
+        )),
+        None => None,
+    };
+    let mut tx = pool.clone().begin().await.map_err(Error::from)?;
+    let applied_transaction = apply_transaction(
+        &mut tx,
+        &transaction,
+        sponsorship
+            .as_ref()
+            .map(|(sponsorship, fee_rate)| (sponsorship, *fee_rate)),
+    )
+    .await?;
+    tx.commit().await.map_err(Error::from)?;
+    for (bitcoin_address, satoshis) in applied_transaction.withdrawals {
+        let _bitcoin_transaction_id =
+            crate::bitcoin::rpc::send_to_address(bitcoin_address, satoshis).await;
+    }
+
+    Ok(applied_transaction.transaction_id)
+}
+
+// Runs every transaction in a single database transaction so that either all
+// of them are applied or none are.
+pub async fn run_transactions(
+    pool: PgPool,
+    transactions: Vec<SignedTransaction>,
+) -> Result<Vec<i64>> {
+    if transactions.is_empty() {
+        return Err(Error::InvalidBatchError(
+            "Batch must contain at least one transaction".to_string(),
+        ));
+    }
+    if transactions.len() > MAX_BATCH_SIZE {
+        return Err(Error::InvalidBatchError(format!(
+            "Batches can't contain more than {} transactions",
+            MAX_BATCH_SIZE
+        )));
+    }
+    for transaction in &transactions {
+        transaction.verify().await?;
+    }
     let mut tx = pool.clone().begin().await.map_err(Error::from)?;
-    let transaction_id = insert_transaction(&mut *tx, &transaction)
+    let mut transaction_ids = vec![];
+    let mut withdrawals = vec![];
+    for transaction in &transactions {
+        let applied_transaction = apply_transaction(&mut tx, transaction, None).await?;
+        transaction_ids.push(applied_transaction.transaction_id);
+        withdrawals.extend(applied_transaction.withdrawals);
+    }
+    tx.commit().await.map_err(Error::from)?;
+    for (bitcoin_address, satoshis) in withdrawals {
+        let _bitcoin_transaction_id =
+            crate::bitcoin::rpc::send_to_address(bitcoin_address, satoshis).await;
+    }
+
+    Ok(transaction_ids)
+}
+
+pub struct AppliedTransaction {
+    pub transaction_id: i64,
+    // Withdrawals to send once the database transaction commits
+    pub withdrawals: Vec<(::bitcoin::Address, i64)>,
+    // Fees charged in each currency
+    pub fees: Vec<(Currency, i64)>,
+}
+
+// Applies a verified transaction inside an open database transaction.
+// Sponsorships come with the fee rate their miner fee has to cover.
+pub async fn apply_transaction(
+    conn: &mut PgConnection,
+    transaction: &SignedTransaction,
+    sponsorship: Option<(&Sponsorship, i64)>,
+) -> Result<AppliedTransaction> {
+    if let Some(valid_until) = transaction.valid_until {
+        if get_current_block(&mut *conn).await?.unwrap_or(0) > valid_until {
+            return Err(Error::ExpiredTransactionError(valid_until));
+        }
+    }
+    let transaction_id = insert_transaction(&mut *conn, transaction)
         .await
         .map_err(Error::from)?;
+    let key_address = transaction.from_address()?;
+    let from_address = get_account_address(&mut *conn, key_address).await?;
+    increment_nonce(&mut *conn, from_address, transaction.nonce).await?;
+    let session_key_id =
+        enforce_session_key(&mut *conn, key_address, &transaction.transaction).await?;
+    let mut withdrawals = vec![];
+    let mut fees = vec![];
 
     match transaction.transaction.clone() {
-        Transaction::Transfer(transaction::Transfer {
-            to: transaction::Address::Stable(to),
-            currency,
-            value,
+        Transaction::Transfer(ref transfer_transaction) => {
+            withdrawals.extend(
+                transfer(
+                    &mut *conn,
+                    transaction_id,
+                    from_address,
+                    transfer_transaction,
+                )
+                .await?,
+            );
+            let fee = charge_fee(
+                &mut *conn,
+                transaction_id,
+                from_address,
+                TRANSFER_FEE,
+                &transfer_transaction.currency,
+                transfer_transaction.value,
+            )
+            .await?;
+            add_fee(&mut fees, &transfer_transaction.currency, fee);
+        }
+        Transaction::BatchTransfer(transaction::BatchTransfer { ref transfers }) => {
+            if transfers.is_empty() {
+                return Err(Error::Error(
+                    "Batch transfer must contain at least one transfer".to_string(),
+                ));
+            }
+            for transfer_transaction in transfers {
+                if transfer_transaction.value <= 0 {
+                    return Err(Error::Error(
+                        "Batch transfers must have positive values".to_string(),
+                    ));
+                }
+                withdrawals.extend(
+                    transfer(
+                        &mut *conn,
+                        transaction_id,
+                        from_address,
+                        transfer_transaction,
+                    )
+                    .await?,
+                );
+                let fee = charge_fee(
+                    &mut *conn,
+                    transaction_id,
+                    from_address,
+                    TRANSFER_FEE,
+                    &transfer_transaction.currency,
+                    transfer_transaction.value,
+                )
+                .await?;
+                add_fee(&mut fees, &transfer_transaction.currency, fee);
+            }
+        }
+        Transaction::ClaimUtxo(ref claim_utxo_transaction) => {
+            claim_utxo(
+                &mut *conn,
+                transaction_id,
+                from_address,
+                claim_utxo_transaction.transaction_id,
+                claim_utxo_transaction.vout,
+                &claim_utxo_transaction.currency,
+            )
+            .await?;
+        }
+
+        Transaction::CreateCheck(ref create_check_transaction) => {
+            create_check(
+                &mut *conn,
+                from_address,
+                transaction_id,
+                create_check_transaction,
+            )
+            .await?;
+            let fee = charge_fee(
+                &mut *conn,
+                transaction_id,
+                from_address,
+                CREATE_CHECK_FEE,
+                &create_check_transaction.currency,
+                create_check_transaction.value,
+            )
+            .await?;
+            add_fee(&mut fees, &create_check_transaction.currency, fee);
+        }
+        Transaction::CashCheck(ref cash_check_transaction) => {
+            cash_check(
+                &mut *conn,
+                from_address,
+                transaction_id,
+                cash_check_transaction,
+            )
+            .await?;
+        }
+        Transaction::VoidCheck(transaction::VoidCheck {
+            transaction_id: check_transaction_id,
         }) => {
-            insert_transfer(
-                &mut *tx,
+            void_check(
+                &mut *conn,
+                from_address,
                 transaction_id,
-                transaction.from_address(),
-                to,
-                &currency,
-                value,
+                check_transaction_id,
             )
-            .await?
+            .await?;
+        }
+        Transaction::LockTransfer(ref lock_transfer_transaction) => {
+            lock_transfer(
+                &mut *conn,
+                from_address,
+                transaction_id,
+                lock_transfer_transaction,
+            )
+            .await?;
+        }
+        Transaction::ClaimLock(ref claim_lock_transaction) => {
+            claim_lock(
+                &mut *conn,
+                from_address,
+                transaction_id,
+                claim_lock_transaction,
+            )
+            .await?;
+        }
+        Transaction::CreateStandingOrder(ref create_standing_order_transaction) => {
+            create_standing_order(
+                &mut *conn,
+                from_address,
+                transaction_id,
+                create_standing_order_transaction,
+            )
+            .await?;
+        }
+        Transaction::CancelStandingOrder(transaction::CancelStandingOrder {
+            transaction_id: standing_order_transaction_id,
+        }) => {
+            cancel_standing_order(&mut *conn, from_address, standing_order_transaction_id).await?;
+        }
+        Transaction::Approve(transaction::Approve {
+            spender,
+            currency,
+            limit,
+        }) => {
+            approve(&mut *conn, from_address, spender, &currency, limit).await?;
         }
-        Transaction::Transfer(transaction::Transfer {
+        Transaction::TransferFrom(transaction::TransferFrom {
+            owner,
+            to,
             currency,
-            to: transaction::Address::Bitcoin(bitcoin_address),
             value,
         }) => {
-            burn(
-                &mut *tx,
+            insert_transfer_from(
+                &mut *conn,
                 transaction_id,
-                transaction.from_address(),
+                from_address,
+                owner,
+                to,
                 &currency,
                 value,
             )
             .await?;
-            let _bitcoin_transaction_id = crate::bitcoin::rpc::send_to_address(
-                ::bitcoin::Address::from_str(&bitcoin_address)
-                    .map_err(Error::from)?
-                    .require_network(Network::Bitcoin)
-                    .map_err(Error::from)?,
-                currency_to_satoshis(&pool, &currency, value).await?,
+        }
+        Transaction::RotateKey(transaction::RotateKey { public_key }) => {
+            rotate_key(&mut *conn, from_address, public_key).await?;
+        }
+        Transaction::CreateInvoice(ref create_invoice_transaction) => {
+            create_invoice(
+                &mut *conn,
+                from_address,
+                transaction_id,
+                create_invoice_transaction,
             )
-            .await;
-            0
+            .await?;
         }
-        Transaction::ClaimUtxo(ref claim_utxo_transaction) => {
-            claim_utxo(
-                &mut *tx,
+        Transaction::RegisterName(transaction::RegisterName { ref name }) => {
+            register_name(&mut *conn, from_address, transaction_id, name).await?;
+        }
+        Transaction::AuthorizeSessionKey(ref authorize_session_key_transaction) => {
+            authorize_session_key(
+                &mut *conn,
+                from_address,
                 transaction_id,
-                transaction.from_address(),
-                claim_utxo_transaction.transaction_id,
-                claim_utxo_transaction.vout,
-                &claim_utxo_transaction.currency,
+                authorize_session_key_transaction,
+            )
+            .await?;
+        }
+        Transaction::SetRecoveryPolicy(ref set_recovery_policy_transaction) => {
+            set_recovery_policy(&mut *conn, from_address, set_recovery_policy_transaction).await?;
+        }
+        Transaction::InitiateRecovery(ref initiate_recovery_transaction) => {
+            initiate_recovery(
+                &mut *conn,
+                from_address,
+                transaction_id,
+                initiate_recovery_transaction,
             )
+            .await?;
+        }
+        Transaction::ApproveRecovery(transaction::ApproveRecovery {
+            transaction_id: recovery_transaction_id,
+        }) => {
+            approve_recovery(&mut *conn, from_address, recovery_transaction_id).await?;
+        }
+        Transaction::CancelRecovery(transaction::CancelRecovery {
+            transaction_id: recovery_transaction_id,
+        }) => {
+            cancel_recovery(&mut *conn, from_address, recovery_transaction_id).await?;
+        }
+        Transaction::FinalizeRecovery(transaction::FinalizeRecovery {
+            transaction_id: recovery_transaction_id,
+        }) => {
+            finalize_recovery(&mut *conn, recovery_transaction_id).await?;
+        }
+        Transaction::RefundLock(transaction::RefundLock {
+            transaction_id: lock_transaction_id,
+        }) => {
+            refund_lock(
+                &mut *conn,
+                from_address,
+                transaction_id,
+                lock_transaction_id,
+            )
+            .await?;
+        }
+    }
+    // Fees are spent by the session key just like the transfers they're
+    // charged on
+    if let Some(session_key_id) = session_key_id {
+        debit_session_key(
+            &mut *conn,
+            session_key_id,
+            fees.iter().map(|(_, fee)| fee).sum(),
+        )
+        .await?;
+    }
+    if let Some((sponsorship, fee_rate)) = sponsorship {
+        if withdrawals.is_empty() {
+            return Err(Error::Error(
+                "Only withdrawals can be sponsored".to_string(),
+            ));
+        }
+        let sponsor_key_address = sponsorship.sponsor_address(transaction)?;
+        if get_session_key(&mut *conn, sponsor_key_address)
             .await?
+            .is_some()
+        {
+            return Err(Error::Error(
+                "Session keys can't sponsor transactions".to_string(),
+            ));
         }
-
-        Transaction::CreateCheck(transaction::CreateCheck {
-            signer,
-            currency,
-            value,
-        }) => insert_transfer(
-            &mut *tx,
+        let sponsor = get_account_address(&mut *conn, sponsor_key_address).await?;
+        sponsor_withdrawal(
+            &mut *conn,
             transaction_id,
-            transaction.from_address(),
-            signer,
-            &currency,
-            value,
+            sponsor,
+            sponsorship,
+            withdrawals.len(),
+            fee_rate,
         )
-        .await
-        .map_err(crate::Error::from)?,
-        Transaction::CashCheck(transaction::CashCheck {
-            transaction_id: check_transaction_id,
-            ..
-        }) => {
-            cash_check(
-                &mut *tx,
-                transaction.from_address(),
+        .await?;
+    }
+    Ok(AppliedTransaction {
+        transaction_id,
+        withdrawals,
+        fees,
+    })
+}
+
+// Runs a transaction without committing it or sending any withdrawals so
+// wallets can see its effects before submitting it.
+pub async fn simulate_transaction(
+    pool: PgPool,
+    transaction: SignedTransaction,
+) -> Result<Simulation> {
+    if let Err(error) = transaction.verify().await {
+        return Ok(Simulation::failed(error));
+    }
+    let mut tx = pool.clone().begin().await.map_err(Error::from)?;
+    let simulation = match apply_transaction(&mut tx, &transaction, None).await {
+        Ok(applied_transaction) => Simulation {
+            balance_deltas: get_balance_deltas(&mut *tx, applied_transaction.transaction_id)
+                .await?,
+            fees: applied_transaction.fees,
+            withdrawals: applied_transaction
+                .withdrawals
+                .into_iter()
+                .map(|(bitcoin_address, satoshis)| (bitcoin_address.to_string(), satoshis))
+                .collect(),
+            error: None,
+        },
+        Err(error) => Simulation::failed(error),
+    };
+    tx.rollback().await?;
+
+    Ok(simulation)
+}
+
+pub async fn get_balance_deltas<'a, E>(pool: E, transaction_id: i64) -> Result<Vec<BalanceDelta>>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query_as(
+        "SELECT account_address(account_id) AS address, currency, SUM(value)::bigint AS value
+        FROM (
+            SELECT recipient_id AS account_id, currency, value FROM ledger WHERE transaction_id = $1
+            UNION ALL
+            SELECT payor_id AS account_id, currency, -value FROM ledger WHERE transaction_id = $1
+        ) AS entries
+        GROUP BY account_id, currency
+        ORDER BY account_id",
+    )
+    .bind(transaction_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn transfer(
+    conn: &mut PgConnection,
+    transaction_id: i64,
+    payor: Address,
+    transfer: &transaction::Transfer,
+) -> Result<Option<(::bitcoin::Address, i64)>> {
+    let to = match &transfer.to {
+        transaction::Address::Stable(to) => *to,
+        transaction::Address::Name(name) => resolve_name(&mut *conn, name).await?,
+        transaction::Address::Bitcoin(_) if transfer.invoice_id.is_some() => {
+            return Err(Error::Error(
+                "Invoices can only be paid to Stable addresses".to_string(),
+            ))
+        }
+        transaction::Address::Bitcoin(bitcoin_address) => {
+            let bitcoin_address =
+                ::bitcoin::Address::from_str(bitcoin_address)?.require_network(Network::Bitcoin)?;
+            burn(
+                &mut *conn,
                 transaction_id,
-                check_transaction_id,
+                payor,
+                &transfer.currency,
+                transfer.value,
+                transfer.memo.as_deref(),
             )
-            .await?
+            .await?;
+            return Ok(Some((
+                bitcoin_address,
+                currency_to_satoshis(&mut *conn, &transfer.currency, transfer.value).await?,
+            )));
         }
     };
-    tx.commit().await.map_err(Error::from)?;
+    let ledger_id = insert_transfer(
+        &mut *conn,
+        transaction_id,
+        payor,
+        to,
+        &transfer.currency,
+        transfer.value,
+        transfer.memo.as_deref(),
+    )
+    .await?;
+    if let Some(invoice_id) = transfer.invoice_id {
+        pay_invoice(&mut *conn, invoice_id, ledger_id, to, transfer).await?;
+    }
+    Ok(None)
+}
+// The sponsor's miner fee has to cover what sending the withdrawals will
+// cost on chain at the given fee rate
+pub async fn sponsor_withdrawal(
+    conn: &mut PgConnection,
+    transaction_id: i64,
+    sponsor: Address,
+    sponsorship: &Sponsorship,
+    withdrawals: usize,
+    fee_rate: i64,
+) -> Result<i64> {
+    let miner_cost = satoshis_to_currency(
+        &mut *conn,
+        &sponsorship.currency,
+        withdrawals as i64 * WITHDRAWAL_VSIZE * fee_rate,
+    )
+    .await?
+    .max(1);
+    if sponsorship.miner_fee < miner_cost {
+        return Err(Error::Error(format!(
+            "Miner fee must be at least {} {}",
+            miner_cost, sponsorship.currency
+        )));
+    }
+    let ledger_id = burn(
+        &mut *conn,
+        transaction_id,
+        sponsor,
+        &sponsorship.currency,
+        sponsorship.miner_fee,
+        None,
+    )
+    .await?;
+    query(
+        "INSERT into sponsorships (transaction_id, sponsor_id, currency, miner_fee, signature)
+        VALUES ($1, account_id($2), $3, $4, $5)",
+    )
+    .bind(transaction_id)
+    .bind(sponsor)
+    .bind(&sponsorship.currency)
+    .bind(sponsorship.miner_fee)
+    .bind(sponsorship.signature)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(ledger_id)
+}
+
+pub async fn burn<'a, E>(
+    pool: E,
+    transaction_id: i64,
+    payor: Address,
+    currency: &Currency,
+    value: i64,
+    memo: Option<&str>,
+) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(insert_transfer(
+        pool,
+        transaction_id,
+        payor,
+        SYSTEM_ADDRESS,
+        &currency,
+        value,
+        memo,
+    )
+    .await?)
+}
+
+pub async fn insert_transaction<'a, E>(pool: E, transaction: &SignedTransaction) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    query(
+        "INSERT into transactions (data, version)
+        VALUES ($1, $2)
+        RETURNING id",
+    )
+    .bind(borsh::to_vec(transaction)?)
+    .bind(PROTOCOL_VERSION as i16)
+    .fetch_one(pool)
+    .await
+    .map(|row| row.get("id"))
+    .map_err(crate::Error::from)
+}
+
+pub async fn get_transaction<'a, E>(pool: E, transaction_id: i64) -> Result<SignedTransaction>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    let row = get_transaction_row(pool, transaction_id).await?;
+    // Legacy CashCheck transactions don't include their value so it's taken
+    // from the ledger
+    decode_transaction(
+        &row.get::<Vec<u8>, _>("data"),
+        row.get("version"),
+        row.get("value"),
+    )
+}
+
+async fn get_transaction_row<'a, E>(pool: E, transaction_id: i64) -> Result<PgRow>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    query(
+        "SELECT
+            data,
+            version,
+            (SELECT COALESCE(SUM(value), 0) FROM ledger WHERE transaction_id = $1)::bigint AS value
+        FROM transactions
+        WHERE id = $1",
+    )
+    .bind(transaction_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::NotFoundError(format!(
+        "Transaction {} not found",
+        transaction_id
+    )))
+}
+
+// Decodes a transaction in the wire format of the version it was stored with
+fn decode_transaction(data: &[u8], version: i16, cashed: i64) -> Result<SignedTransaction> {
+    match version {
+        0 => legacy::decode_stored(data, cashed),
+        _ => Ok(borsh::from_slice(data)?),
+    }
+}
+
+fn decode_transaction_type(data: &[u8], version: i16) -> Result<String> {
+    // The cashed value doesn't change the transaction's type
+    Ok(decode_transaction(data, version, 0)?
+        .transaction
+        .transaction_type()
+        .to_string())
+}
+
+// Checks, locks, invoices and recoveries stay pending until they are
+// settled by a later transaction
+pub async fn get_transaction_status<'a, E>(
+    pool: E,
+    transaction_id: i64,
+) -> Result<TransactionStatus>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    let status: String = query(
+        "SELECT CASE
+            WHEN EXISTS (SELECT 1 FROM recoveries WHERE transaction_id = $1 AND cancelled) THEN 'cancelled'
+            WHEN EXISTS (SELECT 1 FROM recoveries WHERE transaction_id = $1 AND NOT finalized) THEN 'pending'
+            WHEN EXISTS (SELECT 1 FROM invoices WHERE transaction_id = $1 AND ledger_id IS NULL) THEN 'pending'
+            WHEN EXISTS (
+                SELECT 1 FROM checks JOIN ledger ON ledger.id = checks.ledger_id
+                WHERE ledger.transaction_id = $1 AND checks.remaining > 0
+            ) THEN 'pending'
+            WHEN EXISTS (
+                SELECT 1 FROM locks JOIN ledger ON ledger.id = locks.ledger_id
+                WHERE ledger.transaction_id = $1 AND NOT locks.settled
+            ) THEN 'pending'
+            ELSE 'settled'
+        END AS status",
+    )
+    .bind(transaction_id)
+    .fetch_one(pool)
+    .await?
+    .get("status");
+
+    Ok(match status.as_str() {
+        "pending" => TransactionStatus::Pending,
+        "cancelled" => TransactionStatus::Cancelled,
+        _ => TransactionStatus::Settled,
+    })
+}
+
+pub async fn get_transaction_details(
+    pool: &PgPool,
+    transaction_id: i64,
+) -> Result<TransactionDetails> {
+    let row = get_transaction_row(pool, transaction_id).await?;
+    let data: Vec<u8> = row.get("data");
+    let version: i16 = row.get("version");
+    let signed_transaction = decode_transaction(&data, version, row.get("value"))?;
+    let key_address = match version {
+        // Legacy signatures are over the legacy encoding which some legacy
+        // transactions can't be re-encoded into
+        0 => legacy::stored_signer(&data)?,
+        _ => signed_transaction.from_address()?,
+    };
+    // Keys rotated out of an account signed for the account they were the
+    // address of
+    let signer = match get_account_address(pool, key_address).await {
+        Err(Error::InvalidSignatureError(_)) => key_address,
+        result => result?,
+    };
+
+    Ok(TransactionDetails {
+        id: transaction_id,
+        signer,
+        signed_transaction,
+        status: get_transaction_status(pool, transaction_id).await?,
+        ledger_entries: get_transaction_ledger_entries(pool, transaction_id).await?,
+    })
+}
+
+pub async fn increment_nonce(conn: &mut PgConnection, address: Address, nonce: i64) -> Result<()> {
+    query("SELECT account_id($1)")
+        .bind(address)
+        .execute(&mut *conn)
+        .await?;
+    let result = query("UPDATE accounts SET nonce = nonce + 1 WHERE address = $1 AND nonce = $2")
+        .bind(address)
+        .bind(nonce)
+        .execute(&mut *conn)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::BadNonceError(get_nonce(&mut *conn, &address).await?));
+    }
+    Ok(())
+}
+
+pub async fn get_account_address<'a, E>(pool: E, key_address: Address) -> Result<Address>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    let row = query(
+        "SELECT
+            COALESCE(
+                (SELECT address FROM accounts WHERE key_address = $1),
+                (
+                    SELECT account_address(account_id) FROM session_keys
+                    WHERE key_address = $1
+                    AND remaining > 0
+                    AND expires_at > COALESCE(current_block(), 0)
+                )
+            ) AS address,
+            EXISTS (SELECT 1 FROM accounts WHERE address = $1 AND key_address IS NOT NULL) AS rotated",
+    )
+    .bind(key_address)
+    .fetch_one(pool)
+    .await?;
+
+    if let Some(address) = row.get::<Option<Address>, _>("address") {
+        Ok(address)
+    } else if row.get("rotated") {
+        Err(Error::InvalidSignatureError(
+            "Key has been rotated out of this account".to_string(),
+        ))
+    } else {
+        Ok(key_address)
+    }
+}
+
+// A key can only sign for one account so it mustn't already be an account's
+// address, an account's key or a session key
+async fn ensure_key_unused(conn: &mut PgConnection, key_address: Address) -> Result<()> {
+    let used: bool = query(
+        "SELECT
+            EXISTS (SELECT 1 FROM accounts WHERE address = $1 OR key_address = $1)
+            OR EXISTS (SELECT 1 FROM session_keys WHERE key_address = $1) AS used",
+    )
+    .bind(key_address)
+    .fetch_one(&mut *conn)
+    .await?
+    .get("used");
+    if used {
+        return Err(Error::InvalidKeyError(
+            "Key is already in use by another account".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn rotate_key(
+    conn: &mut PgConnection,
+    address: Address,
+    public_key: [u8; 33],
+) -> Result<i64> {
+    // Rotating back to the account's original key is always allowed
+    if Address::from(public_key) != address {
+        ensure_key_unused(&mut *conn, public_key.into()).await?;
+    }
+    Ok(query(
+        "UPDATE accounts SET key_address = NULLIF($2, address)
+        WHERE address = $1
+        RETURNING id",
+    )
+    .bind(address)
+    .bind(Address::from(public_key))
+    .fetch_one(&mut *conn)
+    .await?
+    .get::<i32, _>("id")
+    .into())
+}
+
+pub async fn set_recovery_policy(
+    conn: &mut PgConnection,
+    account: Address,
+    set_recovery_policy: &transaction::SetRecoveryPolicy,
+) -> Result<i64> {
+    let mut guardians = set_recovery_policy.guardians.clone();
+    guardians.sort_by_key(|guardian| guardian.0);
+    guardians.dedup();
+    if !guardians.is_empty()
+        && (set_recovery_policy.threshold == 0
+            || usize::from(set_recovery_policy.threshold) > guardians.len()
+            || set_recovery_policy.delay < 0)
+    {
+        return Err(Error::Error(
+            "Recovery threshold must be between 1 and the number of guardians and delay can't be negative"
+                .to_string(),
+        ));
+    }
+    query("DELETE FROM recovery_policies WHERE account_id = (SELECT id FROM accounts WHERE address = $1)")
+        .bind(account)
+        .execute(&mut *conn)
+        .await?;
+    // An empty set of guardians opts the account back out of recovery
+    if guardians.is_empty() {
+        return Ok(0);
+    }
+    let account_id: i32 = query(
+        "INSERT into recovery_policies (account_id, threshold, delay)
+        VALUES (account_id($1), $2, $3)
+        RETURNING account_id",
+    )
+    .bind(account)
+    .bind(i32::from(set_recovery_policy.threshold))
+    .bind(set_recovery_policy.delay)
+    .fetch_one(&mut *conn)
+    .await?
+    .get("account_id");
+    for guardian in guardians {
+        query(
+            "INSERT into recovery_guardians (account_id, guardian_id)
+            VALUES ($1, account_id($2))",
+        )
+        .bind(account_id)
+        .bind(guardian)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(account_id.into())
+}
+
+pub async fn initiate_recovery(
+    conn: &mut PgConnection,
+    guardian: Address,
+    transaction_id: i64,
+    initiate_recovery: &transaction::InitiateRecovery,
+) -> Result<i64> {
+    query(
+        "INSERT into recoveries (transaction_id, account_id, key_address, executable_at)
+        SELECT $1, recovery_policies.account_id, $3, COALESCE(current_block(), 0) + recovery_policies.delay
+        FROM recovery_policies
+        JOIN recovery_guardians ON recovery_guardians.account_id = recovery_policies.account_id
+        WHERE recovery_policies.account_id = (SELECT id FROM accounts WHERE address = $2)
+        AND recovery_guardians.guardian_id = (SELECT id FROM accounts WHERE address = $4)
+        RETURNING id",
+    )
+    .bind(transaction_id)
+    .bind(initiate_recovery.account)
+    .bind(Address::from(initiate_recovery.public_key))
+    .bind(guardian)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::Error(
+        "Account doesn't have a recovery policy with this guardian".to_string(),
+    ))?;
+    approve_recovery(&mut *conn, guardian, transaction_id).await
+}
+
+pub async fn approve_recovery(
+    conn: &mut PgConnection,
+    guardian: Address,
+    recovery_transaction_id: i64,
+) -> Result<i64> {
+    query(
+        "INSERT into recovery_approvals (recovery_id, guardian_id)
+        SELECT recoveries.id, recovery_guardians.guardian_id
+        FROM recoveries
+        JOIN recovery_guardians ON recovery_guardians.account_id = recoveries.account_id
+        WHERE recoveries.transaction_id = $1
+        AND recoveries.cancelled = false
+        AND recoveries.finalized = false
+        AND recovery_guardians.guardian_id = (SELECT id FROM accounts WHERE address = $2)
+        ON CONFLICT DO NOTHING
+        RETURNING recovery_id",
+    )
+    .bind(recovery_transaction_id)
+    .bind(guardian)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| row.get("recovery_id"))
+    .ok_or(Error::Error(
+        "Recovery isn't pending, signer isn't a guardian or has already approved it".to_string(),
+    ))
+}
+
+pub async fn cancel_recovery(
+    conn: &mut PgConnection,
+    account: Address,
+    recovery_transaction_id: i64,
+) -> Result<i64> {
+    query(
+        "UPDATE recoveries SET cancelled = true
+        WHERE transaction_id = $1
+        AND account_id = (SELECT id FROM accounts WHERE address = $2)
+        AND cancelled = false
+        AND finalized = false
+        RETURNING id",
+    )
+    .bind(recovery_transaction_id)
+    .bind(account)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| row.get("id"))
+    .ok_or(Error::Error(
+        "Recovery doesn't exist or is no longer pending".to_string(),
+    ))
+}
+
+// Anyone can finalize a recovery once enough of the account's current guardians
+// have approved it and its delay has passed.
+pub async fn finalize_recovery(
+    conn: &mut PgConnection,
+    recovery_transaction_id: i64,
+) -> Result<i64> {
+    let row = query(
+        "UPDATE recoveries SET finalized = true
+        WHERE transaction_id = $1
+        AND cancelled = false
+        AND finalized = false
+        AND executable_at <= COALESCE(current_block(), 0)
+        AND (
+            SELECT count(*) FROM recovery_approvals
+            JOIN recovery_guardians ON recovery_guardians.guardian_id = recovery_approvals.guardian_id
+            AND recovery_guardians.account_id = recoveries.account_id
+            WHERE recovery_approvals.recovery_id = recoveries.id
+        ) >= (SELECT threshold FROM recovery_policies WHERE account_id = recoveries.account_id)
+        RETURNING account_id, account_address(account_id) AS address, key_address",
+    )
+    .bind(recovery_transaction_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::Error(
+        "Recovery isn't pending, approved by enough guardians or past its delay".to_string(),
+    ))?;
+    let key_address: Address = row.get("key_address");
+    if key_address != row.get::<Address, _>("address") {
+        ensure_key_unused(&mut *conn, key_address).await?;
+    }
+    Ok(query(
+        "UPDATE accounts SET key_address = NULLIF($2, address)
+        WHERE id = $1
+        RETURNING id",
+    )
+    .bind(row.get::<i32, _>("account_id"))
+    .bind(key_address)
+    .fetch_one(&mut *conn)
+    .await?
+    .get::<i32, _>("id")
+    .into())
+}
+
+// A limit of zero revokes a session key
+pub async fn authorize_session_key(
+    conn: &mut PgConnection,
+    account: Address,
+    transaction_id: i64,
+    authorize_session_key: &transaction::AuthorizeSessionKey,
+) -> Result<()> {
+    if authorize_session_key.limit < 0 {
+        return Err(Error::Error(
+            "Session key limit can't be negative".to_string(),
+        ));
+    }
+    let key_address = Address::from(authorize_session_key.public_key);
+    if authorize_session_key.limit == 0 {
+        query("DELETE FROM session_keys WHERE key_address = $1 AND account_id = account_id($2)")
+            .bind(key_address)
+            .bind(account)
+            .execute(&mut *conn)
+            .await?;
+        return Ok(());
+    }
+    if authorize_session_key.signer(&account)? != key_address {
+        return Err(Error::InvalidSignatureError(
+            "Session key didn't sign its authorization".to_string(),
+        ));
+    }
+    let authorized_account: Option<Address> = query(
+        "SELECT account_address(account_id) AS address FROM session_keys WHERE key_address = $1",
+    )
+    .bind(key_address)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| row.get("address"));
+    if authorized_account != Some(account) {
+        ensure_key_unused(&mut *conn, key_address).await?;
+    }
+    let session_key_id: i64 = query(
+        "INSERT into session_keys (transaction_id, account_id, key_address, currency, remaining, expires_at)
+        VALUES ($1, account_id($2), $3, $4, $5, $6)
+        ON CONFLICT (key_address) DO UPDATE
+        SET transaction_id = $1, currency = $4, remaining = $5, expires_at = $6
+        RETURNING id",
+    )
+    .bind(transaction_id)
+    .bind(account)
+    .bind(key_address)
+    .bind(&authorize_session_key.currency)
+    .bind(authorize_session_key.limit)
+    .bind(authorize_session_key.expires_at)
+    .fetch_one(&mut *conn)
+    .await?
+    .get("id");
+    query("DELETE FROM session_key_recipients WHERE session_key_id = $1")
+        .bind(session_key_id)
+        .execute(&mut *conn)
+        .await?;
+    for recipient in &authorize_session_key.recipients {
+        query(
+            "INSERT into session_key_recipients (session_key_id, recipient_id)
+            VALUES ($1, account_id($2))
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(session_key_id)
+        .bind(recipient)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+// Expired and spent session keys are ignored so their keys sign for
+// themselves again
+async fn get_session_key(conn: &mut PgConnection, key_address: Address) -> Result<Option<PgRow>> {
+    Ok(query(
+        "SELECT id, currency
+        FROM session_keys
+        WHERE key_address = $1
+        AND remaining > 0
+        AND expires_at > COALESCE(current_block(), 0)
+        FOR UPDATE",
+    )
+    .bind(key_address)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+// Session keys can only sign transfers in their currency, to their recipients
+// if any were given, up to their remaining limit. Returns the session key's
+// id if the transaction was signed by one.
+async fn enforce_session_key(
+    conn: &mut PgConnection,
+    key_address: Address,
+    transaction: &Transaction,
+) -> Result<Option<i64>> {
+    let Some(session_key) = get_session_key(&mut *conn, key_address).await? else {
+        return Ok(None);
+    };
+    let session_key_id: i64 = session_key.get("id");
+    let transfers = match transaction {
+        Transaction::Transfer(transfer) => vec![transfer],
+        Transaction::BatchTransfer(transaction::BatchTransfer { transfers }) => {
+            transfers.iter().collect()
+        }
+        _ => {
+            return Err(Error::Error(
+                "Session keys can only sign transfers".to_string(),
+            ))
+        }
+    };
+    for transfer in &transfers {
+        if transfer.currency != session_key.get::<Currency, _>("currency") {
+            return Err(Error::Error(format!(
+                "Session key can't transfer {}",
+                transfer.currency
+            )));
+        }
+        let recipient = match transfer.to {
+            transaction::Address::Stable(to) => Some(to),
+            transaction::Address::Name(ref name) => Some(resolve_name(&mut *conn, name).await?),
+            transaction::Address::Bitcoin(_) => None,
+        };
+        let allowed: bool = query(
+            "SELECT NOT EXISTS (SELECT 1 FROM session_key_recipients WHERE session_key_id = $1)
+            OR EXISTS (
+                SELECT 1 FROM session_key_recipients
+                WHERE session_key_id = $1
+                AND recipient_id = (SELECT id FROM accounts WHERE address = $2)
+            ) AS allowed",
+        )
+        .bind(session_key_id)
+        .bind(recipient)
+        .fetch_one(&mut *conn)
+        .await?
+        .get("allowed");
+        if !allowed {
+            return Err(Error::Error(
+                "Session key can't transfer to this recipient".to_string(),
+            ));
+        }
+    }
+    debit_session_key(
+        &mut *conn,
+        session_key_id,
+        transfers.iter().map(|transfer| transfer.value).sum(),
+    )
+    .await?;
+    Ok(Some(session_key_id))
+}
+
+async fn debit_session_key(conn: &mut PgConnection, session_key_id: i64, value: i64) -> Result<()> {
+    let result = query(
+        "UPDATE session_keys SET remaining = remaining - $2
+        WHERE id = $1 AND remaining >= $2",
+    )
+    .bind(session_key_id)
+    .bind(value)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::Error(
+            "Transfer exceeds the session key's spending limit".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn register_name(
+    conn: &mut PgConnection,
+    account: Address,
+    transaction_id: i64,
+    name: &str,
+) -> Result<i64> {
+    if name.is_empty()
+        || name.len() > MAX_NAME_LENGTH
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+    {
+        return Err(Error::InvalidNameError(format!(
+            "Names must be 1 to {} lowercase letters, digits, underscores or dashes",
+            MAX_NAME_LENGTH
+        )));
+    }
+    // An account can only hold one name so registering a new one releases the old one
+    query("DELETE FROM names WHERE account_id = (SELECT id FROM accounts WHERE address = $1)")
+        .bind(account)
+        .execute(&mut *conn)
+        .await?;
+    query(
+        "INSERT into names (name, account_id, transaction_id)
+        VALUES ($1, account_id($2), $3)
+        ON CONFLICT (name) DO NOTHING
+        RETURNING account_id",
+    )
+    .bind(name)
+    .bind(account)
+    .bind(transaction_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| row.get::<i32, _>("account_id").into())
+    .ok_or(Error::InvalidNameError(format!(
+        "Name {} is already taken",
+        name
+    )))
+}
+
+pub async fn resolve_name<'a, E>(pool: E, name: &str) -> Result<Address>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    query("SELECT account_address(account_id) AS address FROM names WHERE name = $1")
+        .bind(name)
+        .fetch_optional(pool)
+        .await?
+        .map(|row| row.get("address"))
+        .ok_or(Error::NotFoundError(format!(
+            "Name {} isn't registered",
+            name
+        )))
+}
+
+pub async fn get_name<'a, E>(pool: E, address: &Address) -> Result<Option<String>>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query(
+        "SELECT name FROM names
+        WHERE account_id = (SELECT id FROM accounts WHERE address = $1)",
+    )
+    .bind(address)
+    .fetch_optional(pool)
+    .await?
+    .map(|row| row.get("name")))
+}
+
+pub async fn get_nonce<'a, E>(pool: E, address: &Address) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(
+        query("SELECT COALESCE((SELECT nonce FROM accounts WHERE address = $1), 0) as nonce")
+            .bind(address)
+            .fetch_one(pool)
+            .await?
+            .get("nonce"),
+    )
+}
+
+pub async fn insert_transfer<'a, E>(
+    pool: E,
+    transaction_id: i64,
+    payor: Address,
+    recipient: Address,
+    currency: &Currency,
+    value: i64,
+    memo: Option<&str>,
+) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    if memo.is_some_and(|memo| memo.len() > MAX_MEMO_LENGTH) {
+        return Err(Error::InvalidMemoError(format!(
+            "Memo can't be longer than {} bytes",
+            MAX_MEMO_LENGTH
+        )));
+    }
+    query(
+        "INSERT into ledger (transaction_id, payor_id, recipient_id, currency, value, memo)
+        VALUES ($1, account_id($2), account_id($3), $4, $5, $6)
+        RETURNING id",
+    )
+    .bind(transaction_id)
+    .bind(payor)
+    .bind(recipient)
+    .bind(&currency)
+    .bind(value)
+    .bind(memo)
+    .fetch_one(pool)
+    .await
+    .map(|row| row.get("id"))
+    .map_err(crate::Error::from)
+}
+
+pub async fn get_fee<'a, E>(
+    pool: E,
+    transaction_type: &str,
+    currency: &Currency,
+    value: i64,
+) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query(
+        "SELECT COALESCE(
+            (SELECT flat + $3 * basis_points / 10000 FROM fees WHERE transaction_type = $1 AND currency = $2),
+            0
+        ) AS fee",
+    )
+    .bind(transaction_type)
+    .bind(currency)
+    .bind(value)
+    .fetch_one(pool)
+    .await?
+    .get("fee"))
+}
+
+fn add_fee(fees: &mut Vec<(Currency, i64)>, currency: &Currency, fee: i64) {
+    if fee == 0 {
+        return;
+    }
+    match fees
+        .iter_mut()
+        .find(|(fee_currency, _)| fee_currency == currency)
+    {
+        Some((_, total)) => *total += fee,
+        None => fees.push((currency.clone(), fee)),
+    }
+}
+
+pub async fn charge_fee(
+    conn: &mut PgConnection,
+    transaction_id: i64,
+    payor: Address,
+    transaction_type: &str,
+    currency: &Currency,
+    value: i64,
+) -> Result<i64> {
+    let fee = get_fee(&mut *conn, transaction_type, currency, value).await?;
+    if fee > 0 {
+        let node_address = NODE_ADDRESS.ok_or(Error::Error(
+            "Fees can't be charged without a node address".to_string(),
+        ))?;
+        insert_transfer(
+            &mut *conn,
+            transaction_id,
+            payor,
+            node_address,
+            currency,
+            fee,
+            None,
+        )
+        .await?;
+    }
+    Ok(fee)
+}
+
+pub async fn insert_transfer_from<'a, E>(
+    pool: E,
+    transaction_id: i64,
+    spender: Address,
+    payor: Address,
+    recipient: Address,
+    currency: &Currency,
+    value: i64,
+) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    if value <= 0 {
+        return Err(Error::Error(
+            "Transfers must have a positive value".to_string(),
+        ));
+    }
+    query(
+        "INSERT into ledger (transaction_id, payor_id, recipient_id, spender_id, currency, value)
+        VALUES ($1, account_id($2), account_id($3), account_id($4), $5, $6)
+        RETURNING id",
+    )
+    .bind(transaction_id)
+    .bind(payor)
+    .bind(recipient)
+    .bind(spender)
+    .bind(currency)
+    .bind(value)
+    .fetch_one(pool)
+    .await
+    .map(|row| row.get("id"))
+    .map_err(crate::Error::from)
+}
+
+pub async fn approve<'a, E>(
+    pool: E,
+    owner: Address,
+    spender: Address,
+    currency: &Currency,
+    limit: i64,
+) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    if limit < 0 {
+        return Err(Error::Error("Allowance can't be negative".to_string()));
+    }
+    Ok(query(
+        "INSERT into allowances (owner_id, spender_id, currency, value)
+        VALUES (account_id($1), account_id($2), $3, $4)
+        ON CONFLICT (owner_id, spender_id, currency) DO UPDATE SET value = $4
+        RETURNING value",
+    )
+    .bind(owner)
+    .bind(spender)
+    .bind(currency)
+    .bind(limit)
+    .fetch_one(pool)
+    .await?
+    .get("value"))
+}
+
+pub async fn get_allowance<'a, E>(
+    pool: E,
+    owner: &Address,
+    spender: &Address,
+    currency: &Currency,
+) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query(
+        "SELECT COALESCE((SELECT value FROM allowances
+        WHERE owner_id = account_id($1) AND spender_id = account_id($2) AND currency = $3), 0) as value",
+    )
+    .bind(owner)
+    .bind(spender)
+    .bind(currency)
+    .fetch_one(pool)
+    .await?
+    .get("value"))
+}
+
+#[derive(sqlx::FromRow, sqlx::Type)]
+pub struct Utxo {
+    pub transaction_id: Vec<u8>,
+    pub vout: i32,
+    pub value: i64,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct StandingOrder {
+    pub id: i64,
+    pub transaction_id: i64,
+    pub payor: Address,
+    pub recipient: Address,
+    pub currency: Currency,
+    pub value: i64,
+    pub next_block_height: i64,
+}
+
+#[derive(sqlx::FromRow, BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug)]
+pub struct CurrencyInfo {
+    pub currency: Currency,
+    pub decimals: i16,
+    pub enabled: bool,
+}
+
+#[derive(sqlx::FromRow, BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub struct BalanceDelta {
+    pub address: Address,
+    pub currency: Currency,
+    pub value: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub struct Simulation {
+    pub balance_deltas: Vec<BalanceDelta>,
+    pub fees: Vec<(Currency, i64)>,
+    // Bitcoin addresses and the satoshis that would be sent to them
+    pub withdrawals: Vec<(String, i64)>,
+    pub error: Option<String>,
+}
+
+impl Simulation {
+    fn failed(error: Error) -> Self {
+        Self {
+            balance_deltas: vec![],
+            fees: vec![],
+            withdrawals: vec![],
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    Settled,
+    Pending,
+    Cancelled,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub struct TransactionDetails {
+    pub id: i64,
+    pub signed_transaction: SignedTransaction,
+    // The address recovered from the transaction's signatures
+    pub signer: Address,
+    pub status: TransactionStatus,
+    pub ledger_entries: Vec<LedgerEntry>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub struct HistoryEntry {
+    pub ledger_id: i64,
+    pub transaction_id: i64,
+    pub transaction_type: String,
+    pub direction: Direction,
+    pub counterparty: Address,
+    pub currency: Currency,
+    pub value: i64,
+    pub memo: Option<String>,
+    pub standing_order_run: Option<i64>,
+    pub block_height: Option<i64>,
+    // Unix timestamp, unknown for transactions stored before they were recorded
+    pub timestamp: Option<i64>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+    // Pass as the cursor to fetch the next page
+    pub next_cursor: Option<i64>,
+}
+
+#[derive(sqlx::FromRow, sqlx::Type, BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub struct LedgerEntry {
+    pub transaction_id: i64,
+    pub payor: Vec<u8>,
+    pub recipient: Vec<u8>,
+    pub currency: Currency,
+    pub value: i64,
+    pub memo: Option<String>,
+    pub invoice_id: Option<i64>,
+    // Set on payments made by a standing order, which share the transaction
+    // that created it
+    pub standing_order_run: Option<i64>,
+}
+
+pub async fn get_check<'a, E>(
+    pool: E,
+    check_transaction_id: i64,
+) -> Result<transaction::CreateCheck>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    match get_transaction(pool, check_transaction_id)
+        .await?
+        .transaction
+    {
+        Transaction::CreateCheck(create_check) => Ok(create_check),
+        _ => Err(Error::Error(format!(
+            "Transaction {} is not a check",
+            check_transaction_id
+        ))),
+    }
+}
+
+pub async fn create_check(
+    conn: &mut PgConnection,
+    payor: Address,
+    transaction_id: i64,
+    create_check: &transaction::CreateCheck,
+) -> Result<i64> {
+    let ledger_id = insert_transfer(
+        &mut *conn,
+        transaction_id,
+        payor,
+        create_check.signer,
+        &create_check.currency,
+        create_check.value,
+        create_check.memo.as_deref(),
+    )
+    .await?;
+    query("INSERT into checks (ledger_id, remaining) VALUES ($1, $2)")
+        .bind(ledger_id)
+        .bind(create_check.value)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(ledger_id)
+}
+
+pub async fn create_invoice(
+    conn: &mut PgConnection,
+    payee: Address,
+    transaction_id: i64,
+    create_invoice: &transaction::CreateInvoice,
+) -> Result<i64> {
+    if create_invoice.value <= 0 {
+        return Err(Error::Error("Invoice value must be positive".to_string()));
+    }
+    if create_invoice
+        .memo
+        .as_ref()
+        .is_some_and(|memo| memo.len() > MAX_MEMO_LENGTH)
+    {
+        return Err(Error::InvalidMemoError(format!(
+            "Memo can't be longer than {} bytes",
+            MAX_MEMO_LENGTH
+        )));
+    }
+    Ok(query(
+        "INSERT into invoices (transaction_id, payee_id, currency, value, memo, expires_at)
+        VALUES ($1, account_id($2), $3, $4, $5, $6)
+        RETURNING transaction_id",
+    )
+    .bind(transaction_id)
+    .bind(payee)
+    .bind(&create_invoice.currency)
+    .bind(create_invoice.value)
+    .bind(&create_invoice.memo)
+    .bind(create_invoice.expires_at)
+    .fetch_one(&mut *conn)
+    .await?
+    .get("transaction_id"))
+}
+
+pub async fn get_invoice<'a, E>(pool: E, invoice_id: i64) -> Result<Invoice>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    query_as(
+        "SELECT transaction_id AS id, account_address(payee_id) AS payee, currency, value, memo, expires_at, ledger_id IS NOT NULL AS paid
+        FROM invoices
+        WHERE transaction_id = $1",
+    )
+    .bind(invoice_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::NotFoundError(format!(
+        "Invoice {} doesn't exist",
+        invoice_id
+    )))
+}
+
+pub async fn pay_invoice(
+    conn: &mut PgConnection,
+    invoice_id: i64,
+    ledger_id: i64,
+    payee: Address,
+    transfer: &transaction::Transfer,
+) -> Result<i64> {
+    query("SELECT 1 FROM invoices WHERE transaction_id = $1 FOR UPDATE")
+        .bind(invoice_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(Error::NotFoundError(format!(
+            "Invoice {} doesn't exist",
+            invoice_id
+        )))?;
+    query(
+        "UPDATE invoices SET ledger_id = $2
+        WHERE transaction_id = $1
+        AND ledger_id IS NULL
+        AND payee_id = (SELECT id FROM accounts WHERE address = $3)
+        AND currency = $4
+        AND value = $5
+        AND (expires_at IS NULL OR expires_at > COALESCE(current_block(), 0))
+        RETURNING id",
+    )
+    .bind(invoice_id)
+    .bind(ledger_id)
+    .bind(payee)
+    .bind(&transfer.currency)
+    .bind(transfer.value)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| row.get("id"))
+    .ok_or(Error::InvalidInvoiceError(
+        "Invoice has been paid, has expired or doesn't match this transfer".to_string(),
+    ))
+}
+
+pub async fn get_check_remaining<'a, E>(pool: E, check_transaction_id: i64) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query(
+        "SELECT checks.remaining FROM checks
+        JOIN ledger ON ledger.id = checks.ledger_id
+        WHERE ledger.transaction_id = $1",
+    )
+    .bind(check_transaction_id)
+    .fetch_one(pool)
+    .await?
+    .get("remaining"))
+}
+
+pub async fn cash_check(
+    conn: &mut PgConnection,
+    recipient: Address,
+    transaction_id: i64,
+    cash_check: &transaction::CashCheck,
+) -> Result<i64> {
+    let create_check = get_check(&mut *conn, cash_check.transaction_id).await?;
+    if let Some(expires_at) = create_check.expires_at {
+        if get_current_block(&mut *conn).await?.unwrap_or(0) >= expires_at {
+            return Err(Error::InvalidCheckError("Check has expired".to_string()));
+        }
+    }
+    if cash_check.value <= 0 {
+        return Err(Error::InvalidCheckError(
+            "Check must be cashed for a positive value".to_string(),
+        ));
+    }
+    let check = query(
+        "SELECT account_address(recipient_id) AS recipient, ledger.*, checks.remaining
+        FROM ledger
+        JOIN checks ON checks.ledger_id = ledger.id
+        WHERE ledger.transaction_id = $1
+        FOR UPDATE OF checks",
+    )
+    .bind(cash_check.transaction_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    let cashed = check.get::<i64, _>("value") - check.get::<i64, _>("remaining");
+    if cash_check.signer(&recipient, cashed)? != create_check.signer {
+        return Err(Error::InvalidSignatureError(
+            "Check wasn't signed by the check's signer".to_string(),
+        ));
+    }
+    let debited = query(
+        "UPDATE checks SET remaining = remaining - $2
+        WHERE ledger_id = $1 AND remaining >= $2",
+    )
+    .bind(check.get::<i64, _>("id"))
+    .bind(cash_check.value)
+    .execute(&mut *conn)
+    .await?;
+    if debited.rows_affected() == 0 {
+        return Err(Error::InvalidCheckError(
+            "Check doesn't have enough remaining value".to_string(),
+        ));
+    }
+
+    insert_transfer(
+        &mut *conn,
+        transaction_id,
+        check.get("recipient"),
+        recipient,
+        &check.get("currency"),
+        cash_check.value,
+        create_check.memo.as_deref(),
+    )
+    .await
+    .map_err(crate::Error::from)
+}
+
+pub async fn void_check(
+    conn: &mut PgConnection,
+    payor: Address,
+    transaction_id: i64,
+    check_transaction_id: i64,
+) -> Result<i64> {
+    let create_check = get_check(&mut *conn, check_transaction_id).await?;
+    let check = query(
+        "SELECT account_address(payor_id) AS payor, account_address(recipient_id) AS recipient, ledger.*, checks.remaining
+        FROM ledger
+        JOIN checks ON checks.ledger_id = ledger.id
+        WHERE ledger.transaction_id = $1
+        FOR UPDATE OF checks",
+    )
+    .bind(check_transaction_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if check.get::<Address, _>("payor") != payor {
+        return Err(Error::Error(
+            "Only the check's payor can void it".to_string(),
+        ));
+    }
+    let remaining: i64 = check.get("remaining");
+    if remaining == 0 {
+        return Err(Error::Error(
+            "Check has already been cashed or voided".to_string(),
+        ));
+    }
+    query("UPDATE checks SET remaining = 0 WHERE ledger_id = $1")
+        .bind(check.get::<i64, _>("id"))
+        .execute(&mut *conn)
+        .await?;
 
-    return Ok(transaction_id);
-}
-pub async fn burn<'a, E>(
-    pool: E,
-    transaction_id: i64,
-    payor: Address,
-    currency: &Currency,
-    value: i64,
-) -> Result<i64>
-where
-    E: Executor<'a, Database = Postgres>,
-{
-    Ok(insert_transfer(
-        pool,
+    insert_transfer(
+        &mut *conn,
         transaction_id,
+        check.get("recipient"),
         payor,
-        SYSTEM_ADDRESS,
-        &currency,
-        value,
+        &check.get("currency"),
+        remaining,
+        create_check.memo.as_deref(),
     )
-    .await?)
+    .await
 }
 
-pub async fn insert_transaction<'a, E>(pool: E, transaction: &SignedTransaction) -> Result<i64>
+pub async fn get_lock<'a, E>(pool: E, lock_transaction_id: i64) -> Result<transaction::LockTransfer>
 where
     E: Executor<'a, Database = Postgres>,
 {
-    query(
-        "INSERT into transactions (data)
-        VALUES ($1)
-        RETURNING id",
+    match get_transaction(pool, lock_transaction_id)
+        .await?
+        .transaction
+    {
+        Transaction::LockTransfer(lock_transfer) => Ok(lock_transfer),
+        _ => Err(Error::Error(format!(
+            "Transaction {} is not a lock",
+            lock_transaction_id
+        ))),
+    }
+}
+
+pub async fn lock_transfer(
+    conn: &mut PgConnection,
+    payor: Address,
+    transaction_id: i64,
+    lock_transfer: &transaction::LockTransfer,
+) -> Result<i64> {
+    if lock_transfer.value <= 0 {
+        return Err(Error::Error(
+            "Locked transfers must have a positive value".to_string(),
+        ));
+    }
+    let ledger_id = insert_transfer(
+        &mut *conn,
+        transaction_id,
+        payor,
+        ESCROW_ADDRESS,
+        &lock_transfer.currency,
+        lock_transfer.value,
+        None,
     )
-    .bind(borsh::to_vec(transaction)?)
-    .fetch_one(pool)
-    .await
-    .map(|row| row.get("id"))
-    .map_err(crate::Error::from)
+    .await?;
+    query("INSERT into locks (ledger_id) VALUES ($1)")
+        .bind(ledger_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(ledger_id)
 }
 
-pub async fn insert_transfer<'a, E>(
-    pool: E,
+pub async fn settle_lock(
+    conn: &mut PgConnection,
     transaction_id: i64,
-    payor: Address,
+    lock_transaction_id: i64,
     recipient: Address,
-    currency: &Currency,
-    value: i64,
-) -> Result<i64>
-where
-    E: Executor<'a, Database = Postgres>,
-{
-    query(
-        "INSERT into ledger (transaction_id, payor_id, recipient_id, currency, value)
-        VALUES ($1, account_id($2), account_id($3), $4, $5)
-        RETURNING id",
+) -> Result<i64> {
+    let lock = query(
+        "UPDATE locks SET settled = true
+        FROM ledger
+        WHERE ledger.id = locks.ledger_id AND ledger.transaction_id = $1 AND settled = false
+        RETURNING ledger.currency, ledger.value",
+    )
+    .bind(lock_transaction_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::Error(
+        "Lock has already been claimed or refunded".to_string(),
+    ))?;
+
+    insert_transfer(
+        &mut *conn,
+        transaction_id,
+        ESCROW_ADDRESS,
+        recipient,
+        &lock.get("currency"),
+        lock.get("value"),
+        None,
     )
-    .bind(transaction_id)
-    .bind(payor)
-    .bind(recipient)
-    .bind(&currency)
-    .bind(value)
-    .fetch_one(pool)
     .await
-    .map(|row| row.get("id"))
-    .map_err(crate::Error::from)
 }
 
-#[derive(sqlx::FromRow, sqlx::Type)]
-pub struct Utxo {
-    pub transaction_id: Vec<u8>,
-    pub vout: i32,
-    pub value: i64,
+pub async fn claim_lock(
+    conn: &mut PgConnection,
+    recipient: Address,
+    transaction_id: i64,
+    claim_lock: &transaction::ClaimLock,
+) -> Result<i64> {
+    let lock_transfer = get_lock(&mut *conn, claim_lock.transaction_id).await?;
+    if lock_transfer.to != recipient {
+        return Err(Error::Error(
+            "Only the lock's recipient can claim it".to_string(),
+        ));
+    }
+    if <[u8; 32]>::from(Sha256::digest(claim_lock.preimage)) != lock_transfer.hashlock {
+        return Err(Error::Error(
+            "Preimage doesn't match the lock's hashlock".to_string(),
+        ));
+    }
+    if get_current_block(&mut *conn).await?.unwrap_or(0) >= lock_transfer.timeout {
+        return Err(Error::Error("Lock has timed out".to_string()));
+    }
+
+    settle_lock(
+        &mut *conn,
+        transaction_id,
+        claim_lock.transaction_id,
+        recipient,
+    )
+    .await
 }
 
-#[derive(sqlx::FromRow, sqlx::Type)]
-pub struct LedgerEntry {
-    pub payor: Vec<u8>,
-    pub recipient: Vec<u8>,
-    pub currency: Currency,
-    pub value: i64,
+pub async fn refund_lock(
+    conn: &mut PgConnection,
+    payor: Address,
+    transaction_id: i64,
+    lock_transaction_id: i64,
+) -> Result<i64> {
+    let lock_transfer = get_lock(&mut *conn, lock_transaction_id).await?;
+    if get_ledger_entry(&mut *conn, lock_transaction_id)
+        .await?
+        .payor
+        != payor.0
+    {
+        return Err(Error::Error(
+            "Only the lock's payor can refund it".to_string(),
+        ));
+    }
+    if get_current_block(&mut *conn).await?.unwrap_or(0) < lock_transfer.timeout {
+        return Err(Error::Error("Lock hasn't timed out yet".to_string()));
+    }
+
+    settle_lock(&mut *conn, transaction_id, lock_transaction_id, payor).await
 }
 
-pub async fn cash_check(
+pub async fn create_standing_order(
     conn: &mut PgConnection,
-    recipient: Address,
+    payor: Address,
     transaction_id: i64,
-    check_transaction_id: i64,
+    create_standing_order: &transaction::CreateStandingOrder,
 ) -> Result<i64> {
-    let check = query(
-        "SELECT account_address(recipient_id) AS recipient, ledger.* FROM ledger
-        JOIN accounts ON accounts.id = ledger.recipient_id
-        WHERE ledger.transaction_id = $1",
+    if create_standing_order.value <= 0
+        || create_standing_order.interval <= 0
+        || create_standing_order.max_occurrences <= 0
+    {
+        return Err(Error::Error(
+            "Standing order value, interval and max occurrences must be positive".to_string(),
+        ));
+    }
+    Ok(query(
+        "INSERT into standing_orders (transaction_id, payor_id, recipient_id, currency, value, interval, remaining_occurrences, next_block_height)
+        VALUES ($1, account_id($2), account_id($3), $4, $5, $6, $7, COALESCE(current_block(), 0) + $6)
+        RETURNING id",
     )
-    .bind(check_transaction_id)
+    .bind(transaction_id)
+    .bind(payor)
+    .bind(create_standing_order.to)
+    .bind(&create_standing_order.currency)
+    .bind(create_standing_order.value)
+    .bind(create_standing_order.interval)
+    .bind(create_standing_order.max_occurrences)
     .fetch_one(&mut *conn)
+    .await?
+    .get("id"))
+}
+
+pub async fn cancel_standing_order(
+    conn: &mut PgConnection,
+    payor: Address,
+    standing_order_transaction_id: i64,
+) -> Result<i64> {
+    query(
+        "UPDATE standing_orders SET cancelled = true
+        WHERE transaction_id = $1
+        AND payor_id = (SELECT id FROM accounts WHERE address = $2)
+        AND cancelled = false
+        RETURNING id",
+    )
+    .bind(standing_order_transaction_id)
+    .bind(payor)
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| row.get("id"))
+    .ok_or(Error::Error(
+        "Standing order doesn't exist or has already been cancelled".to_string(),
+    ))
+}
+
+pub async fn execute_standing_orders(pool: &PgPool) -> Result<Vec<Address>> {
+    let standing_orders: Vec<StandingOrder> = query_as(
+        "SELECT id, transaction_id, account_address(payor_id) as payor, account_address(recipient_id) as recipient, currency, value, next_block_height
+        FROM standing_orders
+        WHERE cancelled = false AND remaining_occurrences > 0 AND next_block_height <= current_block()
+        ORDER BY id",
+    )
+    .fetch_all(pool)
     .await?;
+    let mut addresses = vec![];
 
-    insert_transfer(
-        &mut *conn,
-        transaction_id,
-        check.get("recipient"),
-        recipient,
-        &check.get("currency"),
-        check.get("value"),
+    for standing_order in standing_orders {
+        let mut tx = pool.begin().await?;
+        if !advance_standing_order(&mut tx, &standing_order).await? {
+            continue;
+        }
+        match insert_transfer(
+            &mut *tx,
+            standing_order.transaction_id,
+            standing_order.payor,
+            standing_order.recipient,
+            &standing_order.currency,
+            standing_order.value,
+            None,
+        )
+        .await
+        {
+            Ok(ledger_id) => {
+                query(
+                    "INSERT into standing_order_runs (standing_order_id, ledger_id, block_height)
+                    VALUES ($1, $2, current_block())",
+                )
+                .bind(standing_order.id)
+                .bind(ledger_id)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+                addresses.push(standing_order.payor);
+                addresses.push(standing_order.recipient);
+            }
+            Err(err) => {
+                tx.rollback().await?;
+                info!("Standing order {} failed: {}", standing_order.id, err);
+                // Failed occurrences still count towards max_occurrences so
+                // an underfunded order isn't retried forever
+                let mut tx = pool.begin().await?;
+                if advance_standing_order(&mut tx, &standing_order).await? {
+                    query(
+                        "INSERT into standing_order_failures (standing_order_id, block_height, reason)
+                        VALUES ($1, current_block(), $2)",
+                    )
+                    .bind(standing_order.id)
+                    .bind(err.to_string())
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                tx.commit().await?;
+            }
+        }
+    }
+
+    Ok(addresses)
+}
+
+// Uses up one occurrence of a standing order. Returns false if the occurrence
+// has already been executed or recorded as failed.
+async fn advance_standing_order(
+    conn: &mut PgConnection,
+    standing_order: &StandingOrder,
+) -> Result<bool> {
+    Ok(query(
+        "UPDATE standing_orders
+        SET remaining_occurrences = remaining_occurrences - 1, next_block_height = next_block_height + interval
+        WHERE id = $1 AND next_block_height = $2 AND cancelled = false AND remaining_occurrences > 0",
     )
-    .await
-    .map_err(crate::Error::from)
+    .bind(standing_order.id)
+    .bind(standing_order.next_block_height)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected()
+        == 1)
 }
 
 pub async fn claim_utxo(
@@ -275,6 +2085,18 @@ where
         .await?
         .get::<i64, _>("value"))
 }
+pub async fn satoshis_to_currency<'a, E>(pool: E, currency: &Currency, value: i64) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    query("SELECT satoshis_to_currency($1, $2) as value")
+        .bind(currency)
+        .bind(value)
+        .fetch_one(pool)
+        .await?
+        .get::<Option<i64>, _>("value")
+        .ok_or(Error::Error(format!("No exchange rate for {}", currency)))
+}
 pub async fn initialize<'a, E>(pool: E) -> Result<()>
 where
     E: Executor<'a, Database = Postgres> + Clone,
@@ -452,6 +2274,40 @@ where
     }
 }
 
+pub async fn get_currencies<'a, E>(pool: E) -> Result<Vec<CurrencyInfo>>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(
+        query_as("SELECT code AS currency, decimals, enabled FROM currencies ORDER BY code")
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+pub async fn get_enabled_currencies<'a, E>(pool: E) -> Result<Vec<Currency>>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(get_currencies(pool)
+        .await?
+        .into_iter()
+        .filter(|currency_info| currency_info.enabled)
+        .map(|currency_info| currency_info.currency)
+        .collect())
+}
+
+pub async fn get_currency<'a, E>(pool: E, currency: &Currency) -> Result<CurrencyInfo>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    query_as("SELECT code AS currency, decimals, enabled FROM currencies WHERE code = $1")
+        .bind(currency)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(Error::Error(format!("Unknown currency {}", currency)))
+}
+
 pub async fn get_currency_decimal_multipler<'a, E>(pool: E, currency: Currency) -> Result<i32>
 where
     E: Executor<'a, Database = Postgres>,
@@ -476,6 +2332,17 @@ where
             .get("value")
     )
 }
+pub async fn get_current_block<'a, E>(pool: E) -> Result<Option<i64>>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query("SELECT current_block() AS height")
+        .fetch_one(pool)
+        .await?
+        .get::<Option<i32>, _>("height")
+        .map(i64::from))
+}
+
 pub async fn get_utxos<'a, E>(pool: E, address: &Address) -> Result<Vec<Utxo>>
 where
     E: Executor<'a, Database = Postgres>,
@@ -495,12 +2362,271 @@ where
     E: Executor<'a, Database = Postgres>,
 {
     Ok(
-        query_as("SELECT account_address(payor_id) as payor, account_address(recipient_id) as recipient, currency, value  from ledger WHERE transaction_id = $1")
+        query_as(
+            "SELECT ledger.transaction_id::bigint, account_address(payor_id) as payor, account_address(recipient_id) as recipient, ledger.currency, ledger.value, ledger.memo, invoices.transaction_id AS invoice_id, standing_order_runs.id AS standing_order_run
+            FROM ledger
+            LEFT JOIN invoices ON invoices.ledger_id = ledger.id
+            LEFT JOIN standing_order_runs ON standing_order_runs.ledger_id = ledger.id
+            WHERE ledger.transaction_id = $1
+            -- Fees are charged after the transaction's own entry
+            ORDER BY ledger.id
+            LIMIT 1",
+        )
             .bind(transaction_id)
             .fetch_one(pool)
             .await?
     )
 }
+
+pub async fn get_ledger_entries<'a, E>(
+    pool: E,
+    address: &Address,
+    currency: &Currency,
+    limit: i64,
+) -> Result<Vec<LedgerEntry>>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query_as(
+        "WITH account AS (SELECT account_id($1) AS id)
+        SELECT ledger.transaction_id::bigint, account_address(payor_id) as payor, account_address(recipient_id) as recipient, ledger.currency, ledger.value, ledger.memo, invoices.transaction_id AS invoice_id, standing_order_runs.id AS standing_order_run
+        FROM ledger
+        JOIN account ON ledger.payor_id = account.id OR ledger.recipient_id = account.id
+        LEFT JOIN invoices ON invoices.ledger_id = ledger.id
+        LEFT JOIN standing_order_runs ON standing_order_runs.ledger_id = ledger.id
+        WHERE ledger.currency = $2
+        ORDER BY ledger.id DESC
+        LIMIT $3",
+    )
+    .bind(address)
+    .bind(currency)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+pub async fn get_transaction_ledger_entries<'a, E>(
+    pool: E,
+    transaction_id: i64,
+) -> Result<Vec<LedgerEntry>>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query_as(
+        "SELECT ledger.transaction_id::bigint, account_address(payor_id) as payor, account_address(recipient_id) as recipient, ledger.currency, ledger.value, ledger.memo, invoices.transaction_id AS invoice_id, standing_order_runs.id AS standing_order_run
+        FROM ledger
+        LEFT JOIN invoices ON invoices.ledger_id = ledger.id
+        LEFT JOIN standing_order_runs ON standing_order_runs.ledger_id = ledger.id
+        WHERE ledger.transaction_id = $1
+        ORDER BY ledger.id",
+    )
+    .bind(transaction_id)
+    .fetch_all(pool)
+    .await?)
+}
+pub struct HistoryFilter {
+    // Only return entries older than this ledger id
+    pub cursor: Option<i64>,
+    pub limit: i64,
+    pub currency: Option<Currency>,
+    pub direction: Option<Direction>,
+    pub from_block: Option<i64>,
+    pub to_block: Option<i64>,
+    // Unix timestamps
+    pub from_time: Option<i64>,
+    pub to_time: Option<i64>,
+}
+
+pub async fn get_history<'a, E>(
+    pool: E,
+    address: &Address,
+    filter: HistoryFilter,
+) -> Result<History>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    let rows = query(
+        "WITH account AS (SELECT id FROM accounts WHERE address = $1)
+        SELECT
+            ledger.id AS ledger_id,
+            ledger.transaction_id::bigint,
+            transactions.data,
+            transactions.version,
+            ledger.payor_id = account.id AS outgoing,
+            account_address(CASE WHEN ledger.payor_id = account.id THEN ledger.recipient_id ELSE ledger.payor_id END) AS counterparty,
+            ledger.currency,
+            ledger.value,
+            ledger.memo,
+            standing_order_runs.id AS standing_order_run,
+            COALESCE(standing_order_runs.block_height, transactions.block_height)::bigint AS block_height,
+            EXTRACT(EPOCH FROM COALESCE(standing_order_runs.created_at, transactions.created_at))::bigint AS timestamp
+        FROM ledger
+        JOIN account ON ledger.payor_id = account.id OR ledger.recipient_id = account.id
+        JOIN transactions ON transactions.id = ledger.transaction_id
+        LEFT JOIN standing_order_runs ON standing_order_runs.ledger_id = ledger.id
+        WHERE ($2::bigint IS NULL OR ledger.id < $2)
+        AND ($3::text IS NULL OR ledger.currency = $3)
+        AND ($4::boolean IS NULL OR (ledger.payor_id = account.id) = $4)
+        AND ($5::bigint IS NULL OR COALESCE(standing_order_runs.block_height, transactions.block_height) >= $5)
+        AND ($6::bigint IS NULL OR COALESCE(standing_order_runs.block_height, transactions.block_height) <= $6)
+        AND ($7::bigint IS NULL OR COALESCE(standing_order_runs.created_at, transactions.created_at) >= to_timestamp($7))
+        AND ($8::bigint IS NULL OR COALESCE(standing_order_runs.created_at, transactions.created_at) <= to_timestamp($8))
+        ORDER BY ledger.id DESC
+        LIMIT $9",
+    )
+    .bind(address)
+    .bind(filter.cursor)
+    .bind(filter.currency)
+    .bind(filter.direction.map(|direction| direction == Direction::Outgoing))
+    .bind(filter.from_block)
+    .bind(filter.to_block)
+    .bind(filter.from_time)
+    .bind(filter.to_time)
+    .bind(filter.limit)
+    .fetch_all(pool)
+    .await?;
+    let entries = rows
+        .iter()
+        .map(|row| {
+            Ok(HistoryEntry {
+                ledger_id: row.get("ledger_id"),
+                transaction_id: row.get("transaction_id"),
+                transaction_type: decode_transaction_type(
+                    &row.get::<Vec<u8>, _>("data"),
+                    row.get("version"),
+                )?,
+                direction: if row.get("outgoing") {
+                    Direction::Outgoing
+                } else {
+                    Direction::Incoming
+                },
+                counterparty: row.get("counterparty"),
+                currency: row.get("currency"),
+                value: row.get("value"),
+                memo: row.get("memo"),
+                standing_order_run: row.get("standing_order_run"),
+                block_height: row.get("block_height"),
+                timestamp: row.get("timestamp"),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let next_cursor = if entries.len() as i64 == filter.limit {
+        entries.last().map(|entry| entry.ledger_id)
+    } else {
+        None
+    };
+
+    Ok(History {
+        entries,
+        next_cursor,
+    })
+}
+
+// Balances are derived backwards from the current balance so that credits
+// made outside of the ledger are treated as having happened before `date`
+pub async fn get_balance_at<'a, E>(
+    pool: E,
+    address: &Address,
+    currency: &Currency,
+    date: NaiveDate,
+) -> Result<i64>
+where
+    E: Executor<'a, Database = Postgres>,
+{
+    Ok(query(
+        "WITH account AS (SELECT id FROM accounts WHERE address = $1)
+        SELECT (
+            COALESCE((SELECT value FROM balances, account WHERE balances.account_id = account.id AND balances.currency = $2), 0)
+            - COALESCE((
+                SELECT SUM(
+                    CASE WHEN ledger.recipient_id = account.id THEN ledger.value ELSE 0 END
+                    - CASE WHEN ledger.payor_id = account.id THEN ledger.value ELSE 0 END
+                )
+                FROM ledger
+                JOIN account ON ledger.payor_id = account.id OR ledger.recipient_id = account.id
+                JOIN transactions ON transactions.id = ledger.transaction_id
+                LEFT JOIN standing_order_runs ON standing_order_runs.ledger_id = ledger.id
+                WHERE ledger.currency = $2
+                AND COALESCE(standing_order_runs.created_at, transactions.created_at) >= ($3::date)::timestamp AT TIME ZONE 'UTC'
+            ), 0)
+        )::bigint AS balance",
+    )
+    .bind(address)
+    .bind(currency)
+    .bind(date.to_string())
+    .fetch_one(pool)
+    .await?
+    .get("balance"))
+}
+
+pub async fn get_statement(
+    pool: &PgPool,
+    address: &Address,
+    currency: &Currency,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Statement> {
+    let entries = query(
+        "WITH account AS (SELECT id FROM accounts WHERE address = $1)
+        SELECT
+            ledger.id AS ledger_id,
+            ledger.transaction_id::bigint,
+            transactions.data,
+            transactions.version,
+            to_char(COALESCE(standing_order_runs.created_at, transactions.created_at) AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS posted_at,
+            account_address(CASE WHEN ledger.payor_id = account.id THEN ledger.recipient_id ELSE ledger.payor_id END) AS counterparty,
+            CASE
+                WHEN ledger.payor_id = ledger.recipient_id THEN 0
+                WHEN ledger.payor_id = account.id THEN -ledger.value
+                ELSE ledger.value
+            END AS value,
+            ledger.memo,
+            standing_order_runs.id AS standing_order_run
+        FROM ledger
+        JOIN account ON ledger.payor_id = account.id OR ledger.recipient_id = account.id
+        JOIN transactions ON transactions.id = ledger.transaction_id
+        LEFT JOIN standing_order_runs ON standing_order_runs.ledger_id = ledger.id
+        WHERE ledger.currency = $2
+        AND COALESCE(standing_order_runs.created_at, transactions.created_at) >= ($3::date)::timestamp AT TIME ZONE 'UTC'
+        AND COALESCE(standing_order_runs.created_at, transactions.created_at) < ($4::date)::timestamp AT TIME ZONE 'UTC'
+        ORDER BY ledger.id",
+    )
+    .bind(address)
+    .bind(currency)
+    .bind(from.to_string())
+    .bind(to.to_string())
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .map(|row| {
+        Ok(StatementEntry {
+            ledger_id: row.get("ledger_id"),
+            transaction_id: row.get("transaction_id"),
+            transaction_type: decode_transaction_type(
+                &row.get::<Vec<u8>, _>("data"),
+                row.get("version"),
+            )?,
+            posted_at: row.get("posted_at"),
+            counterparty: row.get("counterparty"),
+            value: row.get("value"),
+            memo: row.get("memo"),
+            standing_order_run: row.get("standing_order_run"),
+        })
+    })
+    .collect::<Result<Vec<_>>>()?;
+    let decimals = get_currency(pool, currency).await?.decimals;
+
+    Ok(Statement {
+        address: *address,
+        currency: currency.clone(),
+        decimals: decimals as u32,
+        from: from.to_string(),
+        to: to.to_string(),
+        opening_balance: get_balance_at(pool, address, currency, from).await?,
+        closing_balance: get_balance_at(pool, address, currency, to).await?,
+        entries,
+    })
+}
+
 #[cfg(test)]
 pub async fn test_get_balance<'a, E>(pool: E, address: Address, currency: Currency) -> Result<i64>
 where
@@ -16,11 +16,50 @@ pub enum Error {
     InvalidAddressError(String),
     #[error("{0}")]
     IoError(String),
+    #[error("Bad nonce: expected {0}")]
+    BadNonceError(i64),
+    #[error("Invalid Signature Error: {0}")]
+    InvalidSignatureError(String),
+    #[error("Invalid Signing Domain Error: {0}")]
+    InvalidSigningDomainError(String),
+    #[error("Transaction expired at block {0}")]
+    ExpiredTransactionError(i64),
+    #[error("Invalid Check Error: {0}")]
+    InvalidCheckError(String),
+    #[error("Invalid Key Error: {0}")]
+    InvalidKeyError(String),
+    #[error("Invalid Name Error: {0}")]
+    InvalidNameError(String),
+    #[error("Not Found Error: {0}")]
+    NotFoundError(String),
+    #[error("Invalid Memo Error: {0}")]
+    InvalidMemoError(String),
+    #[error("Invalid Invoice Error: {0}")]
+    InvalidInvoiceError(String),
+    #[error("Invalid Batch Error: {0}")]
+    InvalidBatchError(String),
+    #[error("Invalid Statement Error: {0}")]
+    InvalidStatementError(String),
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+        let status = match self {
+            Error::BadNonceError(_)
+            | Error::InvalidSignatureError(_)
+            | Error::InvalidSigningDomainError(_)
+            | Error::ExpiredTransactionError(_)
+            | Error::InvalidCheckError(_)
+            | Error::InvalidKeyError(_)
+            | Error::InvalidNameError(_)
+            | Error::InvalidMemoError(_)
+            | Error::InvalidInvoiceError(_)
+            | Error::InvalidBatchError(_)
+            | Error::InvalidStatementError(_) => StatusCode::BAD_REQUEST,
+            Error::NotFoundError(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
     }
 }
 
@@ -63,8 +102,18 @@ impl From<bitcoin::address::ParseError> for Error {
         Error::Error(err.to_string())
     }
 }
+impl From<k256::ecdsa::Error> for Error {
+    fn from(err: k256::ecdsa::Error) -> Self {
+        Error::InvalidSignatureError(err.to_string())
+    }
+}
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Self {
         Error::Error(err.to_string())
     }
 }
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Error(err.to_string())
+    }
+}
@@ -1,43 +1,55 @@
-use crate::{constants::COIN_MARKET_CAP_KEY, error::Result};
+use crate::{constants::COIN_MARKET_CAP_KEY, error::Result, transaction::Currency};
+use log::{info, warn};
 use reqwest::Client;
 use serde_json::Value;
+use std::collections::HashMap;
 
-pub async fn bitcoin() -> Result<f64> {
+// Fetches the price of one bitcoin in each of the given currencies
+pub async fn bitcoin(currencies: &[Currency]) -> Result<HashMap<Currency, f64>> {
     // Create an HTTP client
     let client = Client::new();
 
     // Perform the GET request
     let response = client
-        .get("https://pro-api.coinmarketcap.com/v2/cryptocurrency/quotes/latest?symbol=BTC")
+        .get("https://pro-api.coinmarketcap.com/v2/cryptocurrency/quotes/latest")
+        .query(&[
+            ("symbol", "BTC".to_string()),
+            (
+                "convert",
+                currencies
+                    .iter()
+                    .map(|currency| currency.to_string().to_uppercase())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        ])
         .header("X-CMC_PRO_API_KEY", COIN_MARKET_CAP_KEY.clone())
         .send()
         .await;
 
     match response {
         Ok(resp) => {
-            // println!("{:?}", resp);
             // Parse the JSON response
             let json: Value = resp.json().await?;
-            // Print the JSON data
-            println!(
-                "Bitcoin Price: {:#}",
-                json.get("data").expect("data").get("BTC").expect("BTC")[0]
-                    .get("quote")
-                    .expect("quote")
-                    .get("USD")
-                    .expect("USD")
-                    .get("price")
-                    .expect("price")
-            );
-            Ok(json.get("data").expect("data").get("BTC").expect("BTC")[0]
+            let quote = json.get("data").expect("data").get("BTC").expect("BTC")[0]
                 .get("quote")
-                .expect("quote")
-                .get("USD")
-                .expect("USD")
-                .get("price")
-                .expect("price")
-                .as_f64()
-                .expect("not f64"))
+                .expect("quote");
+            // Currencies without a quote are left out so the rest can still be
+            // recorded
+            Ok(currencies
+                .iter()
+                .filter_map(|currency| {
+                    let Some(price) = quote
+                        .get(currency.to_string().to_uppercase())
+                        .and_then(|quote| quote.get("price")?.as_f64())
+                    else {
+                        warn!("No bitcoin price for {}", currency);
+                        return None;
+                    };
+                    info!("Bitcoin Price ({}): {:#}", currency, price);
+                    Some((currency.clone(), price))
+                })
+                .collect())
         }
         Err(e) => Err(e.into()),
     }
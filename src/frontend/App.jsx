@@ -92,7 +92,7 @@ function App() {
     if (!publicKey) {
       return;
     }
-    const es = new EventSource(`sse?currency=Usd&address=${Buffer.from(pubKeyToBytes(publicKey)).toString("hex")}`, {
+    const es = new EventSource(`sse?currency=usd&address=${Buffer.from(pubKeyToBytes(publicKey)).toString("hex")}`, {
       withCredentials: true,
     });
     es.onmessage = ({data}) => {
@@ -146,7 +146,7 @@ function App() {
   }
 
   async function claimUtxo(utxo) {
-    stable.claimUtxo(utxo.transaction_id, { Usd: {} }, utxo.vout, privateKey);
+    stable.claimUtxo(utxo.transaction_id, "usd", utxo.vout, privateKey);
   }
 
   const [showQrCodeModal, setShowQrCodeModal] = useState(false);
@@ -45,8 +45,10 @@ function MagicLink({
       {
         CreateCheck: {
           signer: pubKeyToBytes(temporaryPublicKey),
-          currency: { Usd: {} },
+          currency: "usd",
           value: Math.round(parseFloat(inputValue) * 100),
+          expires_at: null,
+          memo: null,
         },
       },
       privateKey,
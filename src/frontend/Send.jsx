@@ -31,9 +31,11 @@ function Send({ address, usdBalance, privateKey }) {
       await stable.postTransaction(
         {
           Transfer: {
-            currency: { Usd: {} },
+            currency: "usd",
             to: addressToObject(recipientAddress),
             value: Math.round(parseFloat(value * 100)),
+            memo: null,
+            invoice_id: null,
           },
         },
         privateKey,
@@ -10,46 +10,75 @@ const { concatBytes } = packedUtils;
 const MAGIC_PREFIX = new Uint8Array([79, 96, 186]);
 export const transactionSchema = BorshSchema.Enum({
   ClaimUtxo: BorshSchema.Struct({
-    currency: BorshSchema.Enum({
-      Usd: BorshSchema.Unit,
-    }),
+    currency: BorshSchema.String,
     transaction_id: BorshSchema.Array(BorshSchema.u8, 32),
     vout: BorshSchema.i32,
   }),
   CreateCheck: BorshSchema.Struct({
     signer: BorshSchema.Array(BorshSchema.u8, 17),
-    currency: BorshSchema.Enum({
-      Usd: BorshSchema.Unit,
-    }),
+    currency: BorshSchema.String,
     value: BorshSchema.i64,
+    expires_at: BorshSchema.Option(BorshSchema.i64),
+    memo: BorshSchema.Option(BorshSchema.String),
   }),
   CashCheck: BorshSchema.Struct({
     transaction_id: BorshSchema.i64,
+    value: BorshSchema.i64,
     signature: BorshSchema.Array(BorshSchema.u8, 65),
   }),
   Transfer: BorshSchema.Struct({
-    currency: BorshSchema.Enum({
-      Usd: BorshSchema.Unit,
-    }),
+    currency: BorshSchema.String,
     to: BorshSchema.Enum({
       BitcoinAddress: BorshSchema.String,
       StableAddress: BorshSchema.Array(BorshSchema.u8, 17),
+      NameAddress: BorshSchema.String,
     }),
     value: BorshSchema.i64,
+    memo: BorshSchema.Option(BorshSchema.String),
+    invoice_id: BorshSchema.Option(BorshSchema.i64),
   }),
 });
-export const transactionAndNonceSchema = BorshSchema.Struct({
+export const SIGNING_DOMAIN_TAG = "stable:transaction";
+export const PROTOCOL_VERSION = 3;
+// Bitcoin network magic bytes
+const MAINNET = new Uint8Array([0xf9, 0xbe, 0xb4, 0xd9]);
+const REGTEST = new Uint8Array([0xfa, 0xbf, 0xb5, 0xda]);
+export const signingDomainSchema = BorshSchema.Struct({
+  version: BorshSchema.u8,
+  network: BorshSchema.Array(BorshSchema.u8, 4),
+});
+export const signingPayloadSchema = BorshSchema.Struct({
+  tag: BorshSchema.String,
+  domain: signingDomainSchema,
   nonce: BorshSchema.i64,
+  valid_until: BorshSchema.Option(BorshSchema.i64),
   transaction: transactionSchema,
 });
 
+export const signaturesSchema = BorshSchema.Enum({
+  Single: BorshSchema.Array(BorshSchema.u8, 65),
+  MultiSig: BorshSchema.Struct({
+    policy: BorshSchema.Struct({
+      threshold: BorshSchema.u8,
+      public_keys: BorshSchema.Vec(BorshSchema.Array(BorshSchema.u8, 33)),
+    }),
+    signatures: BorshSchema.Vec(BorshSchema.Array(BorshSchema.u8, 65)),
+  }),
+});
 export const signedTransactionSchema = BorshSchema.Struct({
   transaction: transactionSchema,
   nonce: BorshSchema.i64,
-  signature: BorshSchema.Array(BorshSchema.u8, 65),
+  valid_until: BorshSchema.Option(BorshSchema.i64),
+  signatures: signaturesSchema,
+  domain: BorshSchema.Option(signingDomainSchema),
 });
 const checkSchema = {
-  struct: { transactionId: "i64", address: { array: { type: "u8", len: 17 } } },
+  struct: {
+    transactionId: "i64",
+    value: "i64",
+    address: { array: { type: "u8", len: 17 } },
+    cashed: "i64",
+  },
 };
 function bech32AddressToBytes(address) {
   const decoded = bech32.decode(address);
@@ -63,7 +92,9 @@ function bech32AddressToBytes(address) {
 }
 
 export function addressToObject(address) {
-  if (address.startsWith("bc1qfast")) {
+  if (address.startsWith("@")) {
+    return { NameAddress: address.slice(1) };
+  } else if (address.startsWith("bc1qfast")) {
     return { StableAddress: bech32AddressToBytes(address).slice(-17) };
   } else {
     return { BitcoinAddress: address };
@@ -159,12 +190,25 @@ export default class StableNetwork {
     );
   }
 
+  async getCheckRemaining(transactionId) {
+    return borsh.deserialize("i64", await this.get(`/checks/${transactionId}`));
+  }
+
   async cashCheck(transactionId, checkPrivateKey, privateKey) {
+    const { transaction } = await (
+      await fetch(`${this.baseUrl}/transactions/${transactionId}/json`)
+    ).json();
+    const value = BigInt(await this.getCheckRemaining(transactionId));
+    // The check's signature covers how much has already been cashed so it
+    // can't be replayed
+    const cashed = BigInt(transaction.CreateCheck.value) - value;
     const signature = secp256k1.sign(
       sha256(
         borsh.serialize(checkSchema, {
           transactionId,
+          value,
           address: pubKeyToBytes(secp256k1.getPublicKey(privateKey)),
+          cashed,
         }),
       ),
       checkPrivateKey,
@@ -173,6 +217,7 @@ export default class StableNetwork {
       {
         CashCheck: {
           transaction_id: transactionId,
+          value,
           signature: concatBytes(
             signature.toCompactRawBytes(),
             new Uint8Array([signature.recovery]),
@@ -182,26 +227,39 @@ export default class StableNetwork {
       privateKey,
     );
   }
+  get signingDomain() {
+    return {
+      version: PROTOCOL_VERSION,
+      network: this.development ? REGTEST : MAINNET,
+    };
+  }
+
   async postTransaction(transaction, privateKey) {
-    const transactionAndNonce = {
+    const signingPayload = {
+      tag: SIGNING_DOMAIN_TAG,
+      domain: this.signingDomain,
       nonce: 0,
+      valid_until: null,
       transaction,
     };
-    // console.log(transactionAndNonce)
     const serliaizedTransaction = borshSerialize(
-      transactionAndNonceSchema,
-      transactionAndNonce,
+      signingPayloadSchema,
+      signingPayload,
     );
     // console.log("--")
     // console.log(Buffer.from(serliaizedTransaction).toString("hex"))
     const signature = secp256k1.sign(sha256(serliaizedTransaction), privateKey);
     let serialized = borshSerialize(signedTransactionSchema, {
-      transaction: transactionAndNonce.transaction,
-      nonce: transactionAndNonce.nonce,
-      signature: secp256k1.etc.concatBytes(
-        signature.toCompactRawBytes(),
-        new Uint8Array([signature.recovery]),
-      ),
+      transaction: signingPayload.transaction,
+      nonce: signingPayload.nonce,
+      valid_until: signingPayload.valid_until,
+      signatures: {
+        Single: secp256k1.etc.concatBytes(
+          signature.toCompactRawBytes(),
+          new Uint8Array([signature.recovery]),
+        ),
+      },
+      domain: signingPayload.domain,
     });
     let response = await this.postRawTransaction(serialized);
 
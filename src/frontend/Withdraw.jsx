@@ -57,9 +57,7 @@ async function sweepWallet(privateKey, depositAddress) {
 const transactionSchema = BorshSchema.Enum({
   Transfer: BorshSchema.Struct({
     nonce: BorshSchema.i64,
-    currency: BorshSchema.Enum({
-      Usd: BorshSchema.Unit,
-    }),
+    currency: BorshSchema.String,
     to: BorshSchema.Array(BorshSchema.u8, 33),
     value: BorshSchema.i64,
   }),
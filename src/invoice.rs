@@ -0,0 +1,79 @@
+use crate::{
+    error::{Error, Result},
+    transaction::Currency,
+    Address,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use reqwest::Url;
+use std::{collections::HashMap, fmt, str::FromStr};
+
+pub const URI_SCHEME: &str = "stable";
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, sqlx::FromRow)]
+pub struct Invoice {
+    pub id: i64,
+    pub payee: Address,
+    pub currency: Currency,
+    pub value: i64,
+    pub memo: Option<String>,
+    pub expires_at: Option<i64>,
+    pub paid: bool,
+}
+
+// Invoices are shared as URIs so they can be encoded in QR codes:
+// stable:<payee>?invoice=<id>&currency=<currency>&value=<value>&memo=<memo>&expires_at=<height>
+impl fmt::Display for Invoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut uri = Url::parse(&format!("{}:{}", URI_SCHEME, hex::encode(self.payee.0)))
+            .map_err(|_| fmt::Error)?;
+        {
+            let mut query = uri.query_pairs_mut();
+            query
+                .append_pair("invoice", &self.id.to_string())
+                .append_pair("currency", &self.currency.to_string())
+                .append_pair("value", &self.value.to_string());
+            if let Some(memo) = &self.memo {
+                query.append_pair("memo", memo);
+            }
+            if let Some(expires_at) = self.expires_at {
+                query.append_pair("expires_at", &expires_at.to_string());
+            }
+        }
+        write!(f, "{}", uri)
+    }
+}
+
+impl FromStr for Invoice {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let uri = Url::parse(input).map_err(|err| Error::Error(err.to_string()))?;
+        if uri.scheme() != URI_SCHEME {
+            return Err(Error::Error(format!("Expected a {} URI", URI_SCHEME)));
+        }
+        let query: HashMap<String, String> = uri.query_pairs().into_owned().collect();
+        let param = |key: &str| {
+            query
+                .get(key)
+                .ok_or(Error::Error(format!("Invoice URI is missing {}", key)))
+        };
+        let number = |key: &str| {
+            param(key)?
+                .parse::<i64>()
+                .map_err(|err| Error::Error(format!("Invalid {}: {}", key, err)))
+        };
+        Ok(Self {
+            id: number("invoice")?,
+            payee: Address(hex::decode(uri.path())?.try_into()?),
+            currency: Currency::from_str(param("currency")?)
+                .map_err(|_| Error::Error("Invalid currency".to_string()))?,
+            value: number("value")?,
+            memo: query.get("memo").cloned(),
+            expires_at: query
+                .contains_key("expires_at")
+                .then(|| number("expires_at"))
+                .transpose()?,
+            paid: false,
+        })
+    }
+}
@@ -0,0 +1,214 @@
+// Wire formats from before transactions were signed over a signing domain.
+// Legacy clients still send these and sign the bare nonce and transaction.
+use crate::{
+    error::{Error, Result},
+    transaction, Signatures,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use std::str::FromStr;
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub enum Currency {
+    Usd,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub enum Address {
+    Bitcoin(String),
+    Stable(crate::Address),
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ClaimUtxo {
+    pub currency: Currency,
+    pub transaction_id: [u8; 32],
+    pub vout: i32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct CreateCheck {
+    pub signer: crate::Address,
+    pub currency: Currency,
+    pub value: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct CashCheck {
+    pub transaction_id: i64,
+    pub signature: [u8; 65],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct Transfer {
+    pub currency: Currency,
+    pub to: Address,
+    pub value: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub enum Transaction {
+    ClaimUtxo(ClaimUtxo),
+    CreateCheck(CreateCheck),
+    CashCheck(CashCheck),
+    Transfer(Transfer),
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct SignedTransaction {
+    pub transaction: Transaction,
+    pub nonce: i64,
+    pub signature: [u8; 65],
+}
+
+impl From<Currency> for transaction::Currency {
+    fn from(currency: Currency) -> Self {
+        match currency {
+            Currency::Usd => transaction::Currency::from_str("usd").unwrap(),
+        }
+    }
+}
+
+impl TryFrom<&transaction::Currency> for Currency {
+    type Error = Error;
+
+    fn try_from(currency: &transaction::Currency) -> Result<Self> {
+        match currency.to_string().as_str() {
+            "usd" => Ok(Self::Usd),
+            _ => Err(Error::InvalidSigningDomainError(format!(
+                "legacy signatures can't transfer {}",
+                currency
+            ))),
+        }
+    }
+}
+
+impl From<Address> for transaction::Address {
+    fn from(address: Address) -> Self {
+        match address {
+            Address::Bitcoin(address) => transaction::Address::Bitcoin(address),
+            Address::Stable(address) => transaction::Address::Stable(address),
+        }
+    }
+}
+
+impl TryFrom<Transaction> for crate::Transaction {
+    type Error = Error;
+
+    fn try_from(transaction: Transaction) -> Result<Self> {
+        Ok(match transaction {
+            Transaction::ClaimUtxo(claim_utxo) => {
+                crate::Transaction::ClaimUtxo(transaction::ClaimUtxo {
+                    currency: claim_utxo.currency.into(),
+                    transaction_id: claim_utxo.transaction_id,
+                    vout: claim_utxo.vout,
+                })
+            }
+            Transaction::CreateCheck(create_check) => {
+                crate::Transaction::CreateCheck(transaction::CreateCheck {
+                    signer: create_check.signer,
+                    currency: create_check.currency.into(),
+                    value: create_check.value,
+                    expires_at: None,
+                    memo: None,
+                })
+            }
+            // Legacy check signatures don't cover the value being cashed so
+            // they could be replayed
+            Transaction::CashCheck(_) => {
+                return Err(Error::InvalidSigningDomainError(
+                    "legacy signatures can't cash checks".to_string(),
+                ))
+            }
+            Transaction::Transfer(transfer) => {
+                crate::Transaction::Transfer(transaction::Transfer {
+                    currency: transfer.currency.into(),
+                    to: transfer.to.into(),
+                    value: transfer.value,
+                    memo: None,
+                    invoice_id: None,
+                })
+            }
+        })
+    }
+}
+
+// Re-encodes a transaction the way a legacy client would have signed it
+impl TryFrom<&crate::Transaction> for Transaction {
+    type Error = Error;
+
+    fn try_from(transaction: &crate::Transaction) -> Result<Self> {
+        match transaction {
+            crate::Transaction::ClaimUtxo(claim_utxo) => Ok(Transaction::ClaimUtxo(ClaimUtxo {
+                currency: (&claim_utxo.currency).try_into()?,
+                transaction_id: claim_utxo.transaction_id,
+                vout: claim_utxo.vout,
+            })),
+            crate::Transaction::CreateCheck(transaction::CreateCheck {
+                signer,
+                currency,
+                value,
+                expires_at: None,
+                memo: None,
+            }) => Ok(Transaction::CreateCheck(CreateCheck {
+                signer: *signer,
+                currency: currency.try_into()?,
+                value: *value,
+            })),
+            crate::Transaction::Transfer(transaction::Transfer {
+                currency,
+                to,
+                value,
+                memo: None,
+                invoice_id: None,
+            }) => Ok(Transaction::Transfer(Transfer {
+                currency: currency.try_into()?,
+                to: match to {
+                    transaction::Address::Bitcoin(address) => Address::Bitcoin(address.clone()),
+                    transaction::Address::Stable(address) => Address::Stable(*address),
+                    transaction::Address::Name(_) => {
+                        return Err(Error::InvalidSigningDomainError(
+                            "legacy signatures can't transfer to names".to_string(),
+                        ))
+                    }
+                },
+                value: *value,
+            })),
+            _ => Err(Error::InvalidSigningDomainError(format!(
+                "legacy signatures can't sign {} transactions",
+                transaction.transaction_type()
+            ))),
+        }
+    }
+}
+
+// Recovers the key that signed a transaction stored in the legacy wire format
+pub fn stored_signer(bytes: &[u8]) -> Result<crate::Address> {
+    let legacy_transaction: SignedTransaction = borsh::from_slice(bytes)?;
+    Ok(transaction::recover(
+        &borsh::to_vec(&(legacy_transaction.nonce, &legacy_transaction.transaction))?,
+        &legacy_transaction.signature,
+    )?
+    .into())
+}
+
+// Decodes a transaction stored in the legacy wire format. Legacy CashCheck
+// transactions don't include the value they cashed so it's passed in.
+pub fn decode_stored(bytes: &[u8], cashed: i64) -> Result<crate::SignedTransaction> {
+    let legacy_transaction: SignedTransaction = borsh::from_slice(bytes)?;
+    Ok(crate::SignedTransaction {
+        transaction: match legacy_transaction.transaction {
+            Transaction::CashCheck(cash_check) => {
+                crate::Transaction::CashCheck(transaction::CashCheck {
+                    transaction_id: cash_check.transaction_id,
+                    value: cashed,
+                    signature: cash_check.signature,
+                })
+            }
+            legacy_transaction => legacy_transaction.try_into()?,
+        },
+        nonce: legacy_transaction.nonce,
+        valid_until: None,
+        signatures: Signatures::Single(legacy_transaction.signature),
+        domain: None,
+    })
+}
@@ -4,11 +4,22 @@ pub mod constants;
 pub mod db;
 mod error;
 pub mod exchange_rates;
+pub mod invoice;
+mod legacy;
+pub mod statement;
 pub mod transaction;
 
 pub use crate::address::Address;
-use crate::transaction::{CashCheck, CreateCheck};
+use crate::transaction::{
+    Approve, ApproveRecovery, AuthorizeSessionKey, BatchTransfer, CancelRecovery,
+    CancelStandingOrder, CashCheck, ClaimLock, CreateCheck, CreateInvoice, CreateStandingOrder,
+    FinalizeRecovery, InitiateRecovery, LockTransfer, MultiSigPolicy, RefundLock, RegisterName,
+    RotateKey, SetRecoveryPolicy, TransferFrom, VoidCheck,
+};
 use crate::{
+    constants::{
+        ACCEPT_LEGACY_SIGNATURES, PROTOCOL_VERSION, SIGNING_DOMAIN_TAG, SPONSOR_DOMAIN_TAG,
+    },
     error::Error,
     transaction::{ClaimUtxo, Currency, Transfer},
 };
@@ -25,9 +36,8 @@ use axum::{
 use borsh::{BorshDeserialize, BorshSerialize};
 #[cfg(test)]
 use k256::ecdsa::SigningKey;
-use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::PgPool;
 use std::sync::Arc;
@@ -44,6 +54,10 @@ use tokio_stream::StreamExt as _;
 use tower_http::cors::AllowOrigin;
 use tower_http::{cors::CorsLayer, services::ServeDir};
 
+const SSE_LEDGER_ENTRIES: i64 = 10;
+const HISTORY_PAGE_SIZE: i64 = 50;
+const MAX_HISTORY_PAGE_SIZE: i64 = 500;
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
@@ -60,8 +74,32 @@ pub async fn app(pool: PgPool) -> Router {
 
     Router::new()
         .route("/transactions", post(insert_transaction))
+        .route(
+            "/transactions/sponsored",
+            post(insert_sponsored_transaction),
+        )
+        .route("/transactions/batch", post(insert_transactions))
+        .route("/transactions/simulate", post(simulate_transaction))
+        .route("/transactions/{transaction_id}", get(get_transaction))
+        .route(
+            "/transactions/{transaction_id}/json",
+            get(get_transaction_json),
+        )
+        .route("/currencies", get(get_currencies))
         .route("/balances/{currency}/{address}", get(get_balance))
+        .route(
+            "/allowances/{currency}/{owner}/{spender}",
+            get(get_allowance),
+        )
         .route("/utxos/{address}", get(get_utxos))
+        .route("/history/{address}", get(get_history))
+        .route("/statements/{currency}/{address}", get(get_statement))
+        .route("/names/{name}", get(get_name_address))
+        .route("/names/reverse/{address}", get(get_address_name))
+        .route("/checks/{transaction_id}", get(get_check))
+        .route("/fees/{transaction_type}/{currency}/{value}", get(get_fee))
+        .route("/invoices/{invoice_id}", get(get_invoice))
+        .route("/invoices/{invoice_id}/uri", get(get_invoice_uri))
         .route("/sse", get(get_sse))
         .route("/{transaction_id}", get(get_magic))
         .route("/images/{amount}", get(get_magic_image))
@@ -98,37 +136,216 @@ impl From<db::Utxo> for Utxo {
     }
 }
 
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
 enum Transaction {
     ClaimUtxo(ClaimUtxo),
     CreateCheck(CreateCheck),
     CashCheck(CashCheck),
     Transfer(Transfer),
+    VoidCheck(VoidCheck),
+    BatchTransfer(BatchTransfer),
+    LockTransfer(LockTransfer),
+    ClaimLock(ClaimLock),
+    RefundLock(RefundLock),
+    CreateStandingOrder(CreateStandingOrder),
+    CancelStandingOrder(CancelStandingOrder),
+    Approve(Approve),
+    TransferFrom(TransferFrom),
+    RotateKey(RotateKey),
+    SetRecoveryPolicy(SetRecoveryPolicy),
+    InitiateRecovery(InitiateRecovery),
+    ApproveRecovery(ApproveRecovery),
+    CancelRecovery(CancelRecovery),
+    FinalizeRecovery(FinalizeRecovery),
+    CreateInvoice(CreateInvoice),
+    AuthorizeSessionKey(AuthorizeSessionKey),
+    RegisterName(RegisterName),
 }
 impl Transaction {
+    fn transaction_type(&self) -> &'static str {
+        match self {
+            Self::ClaimUtxo(_) => "claim_utxo",
+            Self::CreateCheck(_) => "create_check",
+            Self::CashCheck(_) => "cash_check",
+            Self::Transfer(_) => "transfer",
+            Self::VoidCheck(_) => "void_check",
+            Self::BatchTransfer(_) => "batch_transfer",
+            Self::LockTransfer(_) => "lock_transfer",
+            Self::ClaimLock(_) => "claim_lock",
+            Self::RefundLock(_) => "refund_lock",
+            Self::CreateStandingOrder(_) => "create_standing_order",
+            Self::CancelStandingOrder(_) => "cancel_standing_order",
+            Self::Approve(_) => "approve",
+            Self::TransferFrom(_) => "transfer_from",
+            Self::RotateKey(_) => "rotate_key",
+            Self::SetRecoveryPolicy(_) => "set_recovery_policy",
+            Self::InitiateRecovery(_) => "initiate_recovery",
+            Self::ApproveRecovery(_) => "approve_recovery",
+            Self::CancelRecovery(_) => "cancel_recovery",
+            Self::FinalizeRecovery(_) => "finalize_recovery",
+            Self::CreateInvoice(_) => "create_invoice",
+            Self::AuthorizeSessionKey(_) => "authorize_session_key",
+            Self::RegisterName(_) => "register_name",
+        }
+    }
+
     #[cfg(test)]
     fn sign(&self, nonce: i64, signing_key: &SigningKey) -> SignedTransaction {
+        self.sign_with(nonce, signing_key, Some(SigningDomain::bitcoin()), None)
+    }
+
+    #[cfg(test)]
+    fn sign_with(
+        &self,
+        nonce: i64,
+        signing_key: &SigningKey,
+        domain: Option<SigningDomain>,
+        valid_until: Option<i64>,
+    ) -> SignedTransaction {
+        let mut signed_transaction = SignedTransaction {
+            transaction: self.clone(),
+            nonce: nonce,
+            valid_until,
+            signatures: Signatures::Single([0; 65]),
+            domain,
+        };
         let (signature, recovery_id) = signing_key
-            .sign_recoverable(&borsh::to_vec(&(nonce, &self)).unwrap())
-            .unwrap();
-        let signature_bytes: [u8; 65] = [signature.to_bytes().as_slice(), &[recovery_id.to_byte()]]
-            .concat()
-            .try_into()
+            .sign_recoverable(&signed_transaction.signing_payload().unwrap())
             .unwrap();
+        signed_transaction.signatures = Signatures::Single(
+            [signature.to_bytes().as_slice(), &[recovery_id.to_byte()]]
+                .concat()
+                .try_into()
+                .unwrap(),
+        );
+        signed_transaction
+    }
+
+    #[cfg(test)]
+    fn sign_multi_sig(
+        &self,
+        nonce: i64,
+        policy: MultiSigPolicy,
+        signing_keys: &[&SigningKey],
+    ) -> SignedTransaction {
+        let signatures = signing_keys
+            .iter()
+            .map(
+                |signing_key| match self.sign(nonce, signing_key).signatures {
+                    Signatures::Single(signature) => signature,
+                    Signatures::MultiSig(..) => unreachable!(),
+                },
+            )
+            .collect();
         SignedTransaction {
             transaction: self.clone(),
-            nonce: nonce,
-            signature: signature_bytes,
+            nonce,
+            valid_until: None,
+            signatures: Signatures::MultiSig(policy, signatures),
+            domain: Some(SigningDomain::bitcoin()),
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub enum Signatures {
+    Single([u8; 65]),
+    MultiSig(MultiSigPolicy, Vec<[u8; 65]>),
+}
+
+// Ties a signature to a protocol version and bitcoin network so it can't be
+// replayed against a deployment on another network.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy)]
+pub struct SigningDomain {
+    pub version: u8,
+    pub network: [u8; 4],
+}
+
+impl SigningDomain {
+    pub async fn current() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            network: bitcoin::rpc::get_network().await.magic().to_bytes(),
+        }
+    }
+
+    #[cfg(test)]
+    fn bitcoin() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            network: ::bitcoin::Network::Bitcoin.magic().to_bytes(),
         }
     }
 }
+
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct SignedTransaction {
     transaction: Transaction,
     nonce: i64,
+    pub valid_until: Option<i64>,
+    pub signatures: Signatures,
+    pub domain: Option<SigningDomain>,
+}
+
+// A sponsor agrees to cover the miner cost of a withdrawal by signing over the
+// user's signed transaction.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct Sponsorship {
+    pub currency: Currency,
+    pub miner_fee: i64,
     pub signature: [u8; 65],
 }
 
+impl Sponsorship {
+    #[cfg(test)]
+    fn sign(
+        signed_transaction: &SignedTransaction,
+        currency: Currency,
+        miner_fee: i64,
+        signing_key: &SigningKey,
+    ) -> Self {
+        let mut sponsorship = Self {
+            currency,
+            miner_fee,
+            signature: [0; 65],
+        };
+        let (signature, recovery_id) = signing_key
+            .sign_recoverable(&sponsorship.signing_payload(signed_transaction))
+            .unwrap();
+        sponsorship.signature = [signature.to_bytes().as_slice(), &[recovery_id.to_byte()]]
+            .concat()
+            .try_into()
+            .unwrap();
+        sponsorship
+    }
+
+    fn signing_payload(&self, signed_transaction: &SignedTransaction) -> Vec<u8> {
+        borsh::to_vec(&(
+            SPONSOR_DOMAIN_TAG,
+            signed_transaction,
+            &self.currency,
+            self.miner_fee,
+        ))
+        .unwrap()
+    }
+
+    pub fn sponsor_address(
+        &self,
+        signed_transaction: &SignedTransaction,
+    ) -> error::Result<Address> {
+        Ok(
+            transaction::recover(&self.signing_payload(signed_transaction), &self.signature)?
+                .into(),
+        )
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct SponsoredTransaction {
+    pub signed_transaction: SignedTransaction,
+    pub sponsorship: Sponsorship,
+}
+
 // type AppState = PgPool;
 #[derive(Clone)]
 pub struct AppState {
@@ -137,18 +354,74 @@ pub struct AppState {
 }
 
 impl SignedTransaction {
-    pub fn from_address(&self) -> Address {
-        let s: [u8; 64] = self.signature[0..64].try_into().unwrap();
-        let signature = Signature::from_bytes(&s.into()).unwrap();
-        let recovery_id = RecoveryId::from_byte(self.signature[64]).unwrap();
-        VerifyingKey::recover_from_msg(
-            &borsh::to_vec(&(self.nonce, &self.transaction)).unwrap(),
-            &signature,
-            recovery_id,
-        )
-        .unwrap()
-        .try_into()
-        .unwrap()
+    // Transactions from clients that predate signing domains are decoded from
+    // the legacy wire format.
+    pub fn from_slice(bytes: &[u8]) -> error::Result<Self> {
+        borsh::from_slice(bytes).or_else(|_| {
+            let legacy_transaction: legacy::SignedTransaction = borsh::from_slice(bytes)?;
+            Ok(Self {
+                transaction: legacy_transaction.transaction.try_into()?,
+                nonce: legacy_transaction.nonce,
+                valid_until: None,
+                signatures: Signatures::Single(legacy_transaction.signature),
+                domain: None,
+            })
+        })
+    }
+
+    fn signing_payload(&self) -> error::Result<Vec<u8>> {
+        Ok(match self.domain {
+            Some(domain) => borsh::to_vec(&(
+                SIGNING_DOMAIN_TAG,
+                domain,
+                self.nonce,
+                self.valid_until,
+                &self.transaction,
+            )),
+            // Legacy clients sign the bare nonce and transaction in the legacy
+            // wire format
+            None => borsh::to_vec(&(
+                self.nonce,
+                legacy::Transaction::try_from(&self.transaction)?,
+            )),
+        }?)
+    }
+
+    pub fn from_address(&self) -> error::Result<Address> {
+        Ok(match &self.signatures {
+            Signatures::Single(signature) => {
+                transaction::recover(&self.signing_payload()?, signature)?.into()
+            }
+            Signatures::MultiSig(policy, _) => policy.into(),
+        })
+    }
+
+    pub async fn verify(&self) -> error::Result<()> {
+        match self.domain {
+            Some(domain) if domain != SigningDomain::current().await => {
+                return Err(Error::InvalidSigningDomainError(format!(
+                    "expected version {} on the {} network",
+                    PROTOCOL_VERSION,
+                    bitcoin::rpc::get_network().await
+                )))
+            }
+            None if !*ACCEPT_LEGACY_SIGNATURES => {
+                return Err(Error::InvalidSigningDomainError(
+                    "legacy signatures are no longer accepted".to_string(),
+                ))
+            }
+            None if self.valid_until.is_some() => {
+                return Err(Error::InvalidSigningDomainError(
+                    "legacy signatures can't set valid_until".to_string(),
+                ))
+            }
+            _ => (),
+        }
+        let message = self.signing_payload()?;
+        match &self.signatures {
+            Signatures::Single(signature) => transaction::recover(&message, signature).map(|_| ()),
+            Signatures::MultiSig(policy, signatures) => policy.verify(&message, signatures),
+        }
     }
 }
 
@@ -156,23 +429,177 @@ pub async fn insert_transaction(
     State(state): State<AppState>,
     body: Bytes,
 ) -> axum::response::Result<impl IntoResponse> {
-    let transaction: SignedTransaction = borsh::from_slice(&body.to_vec()).map_err(Error::from)?;
-    let transaction_id = db::run_transaction(state.pool.lock().await.clone(), transaction.clone())
-        .await
-        .map_err(Error::from)?;
-    match transaction.transaction {
-        Transaction::Transfer(transaction::Transfer {
-            to: transaction::Address::Stable(to),
-            ..
-        }) => {
+    submit_transaction(&state, SignedTransaction::from_slice(&body)?, None).await
+}
+
+async fn insert_sponsored_transaction(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> axum::response::Result<impl IntoResponse> {
+    let sponsored_transaction: SponsoredTransaction =
+        borsh::from_slice(&body).map_err(Error::from)?;
+    submit_transaction(
+        &state,
+        sponsored_transaction.signed_transaction,
+        Some(sponsored_transaction.sponsorship),
+    )
+    .await
+}
+
+async fn submit_transaction(
+    state: &AppState,
+    transaction: SignedTransaction,
+    sponsorship: Option<Sponsorship>,
+) -> axum::response::Result<Vec<u8>> {
+    let from_address = db::get_account_address(
+        &state.pool.lock().await.clone(),
+        transaction.from_address()?,
+    )
+    .await?;
+    let sponsor_address = match &sponsorship {
+        Some(sponsorship) => Some(
+            db::get_account_address(
+                &state.pool.lock().await.clone(),
+                sponsorship.sponsor_address(&transaction)?,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    let transaction_id = db::run_transaction(
+        state.pool.lock().await.clone(),
+        transaction.clone(),
+        sponsorship,
+    )
+    .await?;
+    if let Some(sponsor_address) = sponsor_address {
+        state
+            .update_channel
+            .lock()
+            .await
+            .0
+            .send(sponsor_address)
+            .unwrap();
+    }
+    notify_transaction(state, from_address, &transaction.transaction).await;
+    Ok(borsh::to_vec(&transaction_id).map_err(Error::from)?)
+}
+
+async fn insert_transactions(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> axum::response::Result<impl IntoResponse> {
+    let transactions: Vec<SignedTransaction> = borsh::from_slice(&body).map_err(Error::from)?;
+    let mut from_addresses = vec![];
+    for transaction in &transactions {
+        from_addresses.push(
+            db::get_account_address(
+                &state.pool.lock().await.clone(),
+                transaction.from_address()?,
+            )
+            .await?,
+        );
+    }
+    let transaction_ids =
+        db::run_transactions(state.pool.lock().await.clone(), transactions.clone()).await?;
+    for (transaction, from_address) in transactions.iter().zip(from_addresses) {
+        notify_transaction(&state, from_address, &transaction.transaction).await;
+    }
+    Ok(borsh::to_vec(&transaction_ids).map_err(Error::from)?)
+}
+
+async fn simulate_transaction(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> axum::response::Result<impl IntoResponse> {
+    let transaction = SignedTransaction::from_slice(&body)?;
+    Ok(borsh::to_vec(
+        &db::simulate_transaction(state.pool.lock().await.clone(), transaction).await?,
+    )
+    .map_err(Error::from)?)
+}
+
+async fn get_transaction(
+    State(state): State<AppState>,
+    axum::extract::Path(transaction_id): axum::extract::Path<i64>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(borsh::to_vec(
+        &db::get_transaction_details(&state.pool.lock().await.clone(), transaction_id).await?,
+    )
+    .map_err(Error::from)?)
+}
+
+async fn get_transaction_json(
+    State(state): State<AppState>,
+    axum::extract::Path(transaction_id): axum::extract::Path<i64>,
+) -> axum::response::Result<impl IntoResponse> {
+    let transaction_details =
+        db::get_transaction_details(&state.pool.lock().await.clone(), transaction_id).await?;
+    let ledger_entries = transaction_details
+        .ledger_entries
+        .iter()
+        .map(|ledger_entry| {
+            json!({
+                "payor": hex::encode(&ledger_entry.payor),
+                "recipient": hex::encode(&ledger_entry.recipient),
+                "currency": ledger_entry.currency,
+                "value": ledger_entry.value.to_string(),
+                "memo": ledger_entry.memo,
+                "invoice_id": ledger_entry.invoice_id,
+                "standing_order_run": ledger_entry.standing_order_run,
+            })
+        })
+        .collect::<Vec<serde_json::Value>>();
+    Ok(axum::Json(json!({
+        "id": transaction_details.id,
+        "transaction": transaction_details.signed_transaction.transaction,
+        "nonce": transaction_details.signed_transaction.nonce,
+        "valid_until": transaction_details.signed_transaction.valid_until,
+        "signer": transaction_details.signer,
+        "status": transaction_details.status,
+        "ledger_entries": ledger_entries,
+    })))
+}
+
+async fn notify_transaction(state: &AppState, from_address: Address, transaction: &Transaction) {
+    match transaction {
+        Transaction::Transfer(transaction::Transfer { ref to, .. }) => {
+            if let Some(to) = recipient_address(&state.pool.lock().await.clone(), to).await {
+                state
+                    .update_channel
+                    .lock()
+                    .await
+                    .0
+                    .send(from_address)
+                    .unwrap();
+                state.update_channel.lock().await.0.send(to).unwrap();
+            }
+        }
+        Transaction::BatchTransfer(transaction::BatchTransfer { ref transfers }) => {
+            let mut addresses = vec![from_address];
+            for transfer in transfers {
+                if let Some(to) =
+                    recipient_address(&state.pool.lock().await.clone(), &transfer.to).await
+                {
+                    if !addresses.contains(&to) {
+                        addresses.push(to);
+                    }
+                }
+            }
+            for address in addresses {
+                state.update_channel.lock().await.0.send(address).unwrap();
+            }
+        }
+        Transaction::TransferFrom(transaction::TransferFrom { owner, to, .. }) => {
             state
                 .update_channel
                 .lock()
                 .await
                 .0
-                .send(transaction.from_address())
+                .send(from_address)
                 .unwrap();
-            state.update_channel.lock().await.0.send(to).unwrap();
+            state.update_channel.lock().await.0.send(*owner).unwrap();
+            state.update_channel.lock().await.0.send(*to).unwrap();
         }
         Transaction::CreateCheck(transaction::CreateCheck { .. }) => {
             state
@@ -180,21 +607,68 @@ pub async fn insert_transaction(
                 .lock()
                 .await
                 .0
-                .send(transaction.from_address())
+                .send(from_address)
                 .unwrap();
         }
-        Transaction::ClaimUtxo(_) => {
+        Transaction::ClaimUtxo(_)
+        | Transaction::VoidCheck(_)
+        | Transaction::LockTransfer(_)
+        | Transaction::ClaimLock(_)
+        | Transaction::RefundLock(_)
+        | Transaction::CancelStandingOrder(_) => {
             state
                 .update_channel
                 .lock()
                 .await
                 .0
-                .send(transaction.from_address())
+                .send(from_address)
                 .unwrap();
         }
         _ => (),
     }
-    Ok(borsh::to_vec(&transaction_id).map_err(Error::from)?)
+}
+
+async fn recipient_address(pool: &PgPool, to: &transaction::Address) -> Option<Address> {
+    match to {
+        transaction::Address::Stable(to) => Some(*to),
+        transaction::Address::Name(name) => db::resolve_name(pool, name).await.ok(),
+        transaction::Address::Bitcoin(_) => None,
+    }
+}
+
+async fn get_name_address(
+    State(state): State<AppState>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(
+        borsh::to_vec(&db::resolve_name(&state.pool.lock().await.clone(), &name).await?)
+            .map_err(Error::from)
+            .into_response(),
+    )
+}
+
+async fn get_address_name(
+    State(state): State<AppState>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(borsh::to_vec(
+        &db::get_name(
+            &state.pool.lock().await.clone(),
+            &Address(hex::decode(&address).map_err(Error::from)?.try_into()?),
+        )
+        .await?,
+    )
+    .map_err(Error::from)
+    .into_response())
+}
+
+async fn get_currencies(
+    State(state): State<AppState>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(
+        borsh::to_vec(&db::get_currencies(&state.pool.lock().await.clone()).await?)
+            .map_err(Error::from)?,
+    )
 }
 
 async fn get_balance(
@@ -212,6 +686,77 @@ async fn get_balance(
     .map_err(Error::from)
     .into_response())
 }
+
+async fn get_allowance(
+    State(state): State<AppState>,
+    axum::extract::Path((currency, owner, spender)): axum::extract::Path<(String, String, String)>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(borsh::to_vec(
+        &db::get_allowance(
+            &state.pool.lock().await.clone(),
+            &Address(hex::decode(&owner).map_err(Error::from)?.try_into()?),
+            &Address(hex::decode(&spender).map_err(Error::from)?.try_into()?),
+            &Currency::from_str(&currency)?,
+        )
+        .await?,
+    )
+    .map_err(Error::from)
+    .into_response())
+}
+
+async fn get_fee(
+    State(state): State<AppState>,
+    axum::extract::Path((transaction_type, currency, value)): axum::extract::Path<(
+        String,
+        String,
+        i64,
+    )>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(borsh::to_vec(
+        &db::get_fee(
+            &state.pool.lock().await.clone(),
+            &transaction_type,
+            &Currency::from_str(&currency)?,
+            value,
+        )
+        .await?,
+    )
+    .map_err(Error::from)
+    .into_response())
+}
+
+async fn get_invoice(
+    State(state): State<AppState>,
+    axum::extract::Path(invoice_id): axum::extract::Path<i64>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(
+        borsh::to_vec(&db::get_invoice(&state.pool.lock().await.clone(), invoice_id).await?)
+            .map_err(Error::from)
+            .into_response(),
+    )
+}
+
+async fn get_invoice_uri(
+    State(state): State<AppState>,
+    axum::extract::Path(invoice_id): axum::extract::Path<i64>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(
+        db::get_invoice(&state.pool.lock().await.clone(), invoice_id)
+            .await?
+            .to_string(),
+    )
+}
+
+async fn get_check(
+    State(state): State<AppState>,
+    axum::extract::Path(transaction_id): axum::extract::Path<i64>,
+) -> axum::response::Result<impl IntoResponse> {
+    Ok(borsh::to_vec(
+        &db::get_check_remaining(&state.pool.lock().await.clone(), transaction_id).await?,
+    )
+    .map_err(Error::from)
+    .into_response())
+}
 struct HtmlTemplate<T>(T);
 
 impl<T> IntoResponse for HtmlTemplate<T>
@@ -238,6 +783,73 @@ async fn get_index() -> axum::response::Result<impl IntoResponse> {
     Ok(HtmlTemplate(template))
 }
 
+#[derive(Deserialize)]
+struct HistoryParams {
+    cursor: Option<i64>,
+    limit: Option<i64>,
+    currency: Option<Currency>,
+    direction: Option<db::Direction>,
+    from_block: Option<i64>,
+    to_block: Option<i64>,
+    from_time: Option<i64>,
+    to_time: Option<i64>,
+}
+
+async fn get_history(
+    State(state): State<AppState>,
+    axum::extract::Path(address): axum::extract::Path<String>,
+    history_params: Query<HistoryParams>,
+) -> axum::response::Result<impl IntoResponse> {
+    let history_params = history_params.0;
+    Ok(borsh::to_vec(
+        &db::get_history(
+            &state.pool.lock().await.clone(),
+            &Address(hex::decode(&address).map_err(Error::from)?.try_into()?),
+            db::HistoryFilter {
+                cursor: history_params.cursor,
+                limit: history_params
+                    .limit
+                    .unwrap_or(HISTORY_PAGE_SIZE)
+                    .clamp(1, MAX_HISTORY_PAGE_SIZE),
+                currency: history_params.currency,
+                direction: history_params.direction,
+                from_block: history_params.from_block,
+                to_block: history_params.to_block,
+                from_time: history_params.from_time,
+                to_time: history_params.to_time,
+            },
+        )
+        .await?,
+    )
+    .map_err(Error::from)?)
+}
+
+#[derive(Deserialize)]
+struct StatementParams {
+    from: String,
+    to: String,
+    format: Option<String>,
+}
+
+async fn get_statement(
+    State(state): State<AppState>,
+    axum::extract::Path((currency, address)): axum::extract::Path<(String, String)>,
+    statement_params: Query<StatementParams>,
+) -> axum::response::Result<impl IntoResponse> {
+    let format = statement::Format::from_str(statement_params.format.as_deref().unwrap_or("json"))?;
+    let statement = db::get_statement(
+        &state.pool.lock().await.clone(),
+        &Address(hex::decode(&address).map_err(Error::from)?.try_into()?),
+        &Currency::from_str(&currency)?,
+        statement::parse_date(&statement_params.from)?,
+        statement::parse_date(&statement_params.to)?,
+    )
+    .await?;
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, format.content_type().parse().unwrap());
+    Ok((headers, statement.render(format)?))
+}
+
 #[derive(Deserialize)]
 struct SseParams {
     currency: Currency,
@@ -301,12 +913,29 @@ async fn send_state(
             })
         })
         .collect::<Vec<serde_json::Value>>();
+    let ledger_entries = &db::get_ledger_entries(pool, address, currency, SSE_LEDGER_ENTRIES)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|ledger_entry| {
+            json!({
+                "transaction_id": ledger_entry.transaction_id,
+                "payor": hex::encode(ledger_entry.payor),
+                "recipient": hex::encode(ledger_entry.recipient),
+                "value": ledger_entry.value.to_string(),
+                "memo": ledger_entry.memo,
+                "invoice_id": ledger_entry.invoice_id,
+                "standing_order_run": ledger_entry.standing_order_run,
+            })
+        })
+        .collect::<Vec<serde_json::Value>>();
     let balance = db::get_balance(pool, &address, &currency).await.unwrap();
     tx.send(
         Event::default()
             .json_data(json!({
                     "balance": balance.to_string(),
-                    "utxos": utxos
+                    "utxos": utxos,
+                    "ledger_entries": ledger_entries
 
             }))
             .unwrap(),
@@ -395,7 +1024,14 @@ mod tests {
     use super::*;
     use crate::{
         address::Address,
-        transaction::{CashCheck, CreateCheck, Currency, Transfer},
+        invoice::Invoice,
+        transaction::{
+            Approve, ApproveRecovery, AuthorizeSessionKey, BatchTransfer, CancelRecovery,
+            CancelStandingOrder, CashCheck, ClaimLock, CreateCheck, CreateInvoice,
+            CreateStandingOrder, Currency, FinalizeRecovery, InitiateRecovery, LockTransfer,
+            MultiSigPolicy, RefundLock, RegisterName, RotateKey, SetRecoveryPolicy, Transfer,
+            TransferFrom, VoidCheck,
+        },
     };
     use ::bitcoin::consensus::Decodable;
     use axum::{
@@ -410,6 +1046,7 @@ mod tests {
     use rust_decimal::Decimal;
     use secp256k1::rand::rngs::OsRng;
     use serde_json::json;
+    use sha2::{Digest, Sha256};
     use sqlx::PgPool;
     use std::{collections::HashMap, env, fs::File, io::Read};
     use tower::ServiceExt;
@@ -450,6 +1087,27 @@ mod tests {
         pub static ref CHECK_ADDRESS: Address = VerifyingKey::from(CHECK_SECRET_KEY.clone())
             .try_into()
             .unwrap();
+        pub static ref BITCOIND: MockServer = {
+            let server = MockServer::start();
+            env::set_var("BITCOIND_URL", server.url(""));
+            server.mock(|when, then| {
+                when.method("POST").body_includes("getblockchaininfo");
+                then.status(200)
+                    .header("content-type", "text/json; charset=UTF-8")
+                    .body(json!({"error": null, "result": {"chain": "main"}}).to_string());
+            });
+            server
+        };
+        // Fees are paid to the address of the node's key
+        pub static ref NODE_ADDRESS: Address = {
+            env::set_var(
+                "PRIVATE_KEY",
+                ::bitcoin::PrivateKey::from_slice(&[2; 32], ::bitcoin::Network::Bitcoin)
+                    .unwrap()
+                    .to_wif(),
+            );
+            constants::NODE_ADDRESS.unwrap()
+        };
     }
     macro_rules! bitcoin_block {
         ($file_name:expr) => {{
@@ -466,122 +1124,1574 @@ mod tests {
         }};
     }
 
-    #[sqlx::test]
-    async fn test_transfer(pool: PgPool) {
-        db::credit(&pool, *ALICE, Currency::Usd, 10000)
-            .await
-            .unwrap();
-        let transaction = Transaction::Transfer(Transfer {
-            currency: Currency::Usd,
+    fn usd() -> Currency {
+        Currency::from_str("usd").unwrap()
+    }
+
+    // Signing domains are checked against the network reported by bitcoind
+    async fn app(pool: PgPool) -> Router {
+        lazy_static::initialize(&BITCOIND);
+        lazy_static::initialize(&NODE_ADDRESS);
+        super::app(pool).await
+    }
+
+    async fn post_transaction(
+        pool: &PgPool,
+        signed_transaction: &SignedTransaction,
+    ) -> axum::response::Response {
+        let request = Request::builder()
+            .method("POST")
+            .header("content-type", "application/octet-stream")
+            .uri("/transactions")
+            .body(Body::from(borsh::to_vec(signed_transaction).unwrap()))
+            .unwrap();
+
+        app(pool.clone()).await.oneshot(request).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_transfer(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 10000,
+            memo: None,
+            invoice_id: None,
+        });
+        let _transaction2 = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: transaction::Address::Bitcoin("36sTjLr6VTRfF5MQGTH3BVVeDH17aEwQQW".to_string()),
+            value: 4,
+            memo: None,
+            invoice_id: None,
+        });
+        // println!("{}", hex::encode(borsh::to_vec(&(2i64, transaction2)).unwrap()));
+
+        let signed_transaction = transaction.sign(0, &ALICES_SECRET_KEY.clone());
+        let request = Request::builder()
+            .method("POST")
+            .header("content-type", "application/octet-stream")
+            .uri("/transactions")
+            .body(Body::from(borsh::to_vec(&signed_transaction).unwrap()))
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/balances/usd/{}", hex::encode((*ALICE).0)))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<i64>(&body).unwrap(), 0);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/balances/usd/{}", hex::encode((*BOB).0)))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<i64>(&body).unwrap(), 10000);
+    }
+
+    #[sqlx::test]
+    async fn test_transfer_fee(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        sqlx::query(
+            "INSERT into fees (transaction_type, currency, flat, basis_points) VALUES ('transfer', 'usd', 10, 100)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let request = Request::builder()
+            .method("GET")
+            .uri("/fees/transfer/usd/4000")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<i64>(&body).unwrap(), 50);
+
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 4000,
+            memo: None,
+            invoice_id: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 5950);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 4000);
+        assert_eq!(
+            db::get_balance(&pool, &NODE_ADDRESS, &usd()).await.unwrap(),
+            50
+        );
+
+        sqlx::query(
+            "INSERT into fees (transaction_type, currency, flat, basis_points) VALUES ('create_check', 'usd', 10, 0)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let transaction = Transaction::CreateCheck(CreateCheck {
+            currency: usd(),
+            value: 1000,
+            signer: *CHECK_ADDRESS,
+            expires_at: None,
+            memo: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(1, &ALICES_SECRET_KEY.clone())).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/{}", transaction_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        // The magic link shows the check rather than its fee
+        assert!(String::from_utf8_lossy(&body).contains("$10.00 on the Stable Network"));
+
+        let check = Transaction::CashCheck(CashCheck::sign(
+            transaction_id,
+            1000,
+            *BOB,
+            0,
+            &CHECK_SECRET_KEY,
+        ));
+        let response = post_transaction(&pool, &check.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 4940);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 5000);
+        assert_eq!(
+            db::get_balance(&pool, &NODE_ADDRESS, &usd()).await.unwrap(),
+            60
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_signing_domain(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 4000,
+            memo: None,
+            invoice_id: None,
+        });
+        let response = post_transaction(
+            &pool,
+            &transaction.sign_with(
+                0,
+                &ALICES_SECRET_KEY.clone(),
+                Some(SigningDomain {
+                    version: PROTOCOL_VERSION,
+                    network: ::bitcoin::Network::Testnet.magic().to_bytes(),
+                }),
+                None,
+            ),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let mut invalid_recovery_id = transaction.sign(0, &ALICES_SECRET_KEY.clone());
+        if let Signatures::Single(ref mut signature) = invalid_recovery_id.signatures {
+            signature[64] = 4;
+        }
+        let response = post_transaction(&pool, &invalid_recovery_id).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Version 1 envelopes had no valid_until
+        let response = post_transaction(
+            &pool,
+            &transaction.sign_with(
+                0,
+                &ALICES_SECRET_KEY.clone(),
+                Some(SigningDomain {
+                    version: 1,
+                    network: ::bitcoin::Network::Bitcoin.magic().to_bytes(),
+                }),
+                None,
+            ),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let legacy_transaction = legacy::Transaction::Transfer(legacy::Transfer {
+            currency: legacy::Currency::Usd,
+            to: legacy::Address::Stable(*BOB),
+            value: 4000,
+        });
+        let (signature, recovery_id) = ALICES_SECRET_KEY
+            .sign_recoverable(&borsh::to_vec(&(0i64, &legacy_transaction)).unwrap())
+            .unwrap();
+        let legacy_bytes = borsh::to_vec(&legacy::SignedTransaction {
+            transaction: legacy_transaction,
+            nonce: 0,
+            signature: [signature.to_bytes().as_slice(), &[recovery_id.to_byte()]]
+                .concat()
+                .try_into()
+                .unwrap(),
+        })
+        .unwrap();
+        let decoded_transaction = SignedTransaction::from_slice(&legacy_bytes).unwrap();
+
+        assert_eq!(decoded_transaction.transaction, transaction);
+        assert_eq!(decoded_transaction.domain, None);
+        assert_eq!(decoded_transaction.from_address().unwrap(), *ALICE);
+
+        let request = Request::builder()
+            .method("POST")
+            .header("content-type", "application/octet-stream")
+            .uri("/transactions")
+            .body(Body::from(legacy_bytes))
+            .unwrap();
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        // Legacy signatures are rejected unless ACCEPT_LEGACY_SIGNATURES is set
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 6000);
+    }
+
+    #[sqlx::test]
+    async fn test_expired_transaction(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        for _ in 0..2 {
+            sqlx::query("INSERT into blocks DEFAULT VALUES")
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 4000,
+            memo: None,
+            invoice_id: None,
+        });
+        let response = post_transaction(
+            &pool,
+            &transaction.sign_with(
+                0,
+                &ALICES_SECRET_KEY.clone(),
+                Some(SigningDomain::bitcoin()),
+                Some(1),
+            ),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_transaction(
+            &pool,
+            &transaction.sign_with(
+                0,
+                &ALICES_SECRET_KEY.clone(),
+                Some(SigningDomain::bitcoin()),
+                Some(2),
+            ),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 6000);
+    }
+
+    #[sqlx::test]
+    async fn test_invoice(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::CreateInvoice(CreateInvoice {
+            currency: usd(),
+            value: 4000,
+            memo: Some("Lunch & coffee".to_string()),
+            expires_at: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let invoice_id: i64 =
+            borsh::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/invoices/{}/uri", invoice_id))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let invoice = Invoice::from_str(std::str::from_utf8(&body).unwrap()).unwrap();
+
+        assert!(std::str::from_utf8(&body).unwrap().starts_with("stable:"));
+        assert_eq!(invoice, db::get_invoice(&pool, invoice_id).await.unwrap());
+
+        let mut transfer = Transfer {
+            currency: invoice.currency,
+            to: crate::transaction::Address::Stable(invoice.payee),
+            value: 3000,
+            memo: invoice.memo,
+            invoice_id: Some(invoice.id),
+        };
+        let response = post_transaction(
+            &pool,
+            &Transaction::Transfer(transfer.clone()).sign(0, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        transfer.value = invoice.value;
+        let response = post_transaction(
+            &pool,
+            &Transaction::Transfer(transfer.clone()).sign(0, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/invoices/{}", invoice_id))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert!(from_slice::<Invoice>(&body).unwrap().paid);
+        assert_eq!(
+            db::get_ledger_entries(&pool, &BOB, &usd(), 1)
+                .await
+                .unwrap()[0]
+                .invoice_id,
+            Some(invoice_id)
+        );
+
+        let response = post_transaction(
+            &pool,
+            &Transaction::Transfer(transfer).sign(1, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 4000,
+            memo: None,
+            invoice_id: Some(1000000),
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri("/invoices/1000000")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 6000);
+    }
+
+    #[sqlx::test]
+    async fn test_names(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let register_name = |name: &str| {
+            Transaction::RegisterName(RegisterName {
+                name: name.to_string(),
+            })
+        };
+        let response = post_transaction(
+            &pool,
+            &register_name("Bob!").sign(0, &BOBS_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_transaction(
+            &pool,
+            &register_name("bob").sign(0, &BOBS_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = post_transaction(
+            &pool,
+            &register_name("bob").sign(0, &BURNS_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri("/names/bob")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<Address>(&body).unwrap(), *BOB);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/names/reverse/{}", hex::encode((*BOB).0)))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(
+            from_slice::<Option<String>>(&body).unwrap(),
+            Some("bob".to_string())
+        );
+
+        let transfer = |name: &str| {
+            Transaction::Transfer(Transfer {
+                currency: usd(),
+                to: crate::transaction::Address::Name(name.to_string()),
+                value: 4000,
+                memo: None,
+                invoice_id: None,
+            })
+        };
+        let response = post_transaction(
+            &pool,
+            &transfer("nobody").sign(0, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response =
+            post_transaction(&pool, &transfer("bob").sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 4000);
+    }
+
+    #[sqlx::test]
+    async fn test_transaction_batch(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transfer = |to, value| {
+            Transaction::Transfer(Transfer {
+                currency: usd(),
+                to: crate::transaction::Address::Stable(to),
+                value,
+                memo: None,
+                invoice_id: None,
+            })
+        };
+        let post_batch = |transactions: Vec<SignedTransaction>| {
+            let pool = pool.clone();
+            async move {
+                let request = Request::builder()
+                    .method("POST")
+                    .header("content-type", "application/octet-stream")
+                    .uri("/transactions/batch")
+                    .body(Body::from(borsh::to_vec(&transactions).unwrap()))
+                    .unwrap();
+                app(pool).await.oneshot(request).await.unwrap()
+            }
+        };
+        let response = post_batch(vec![
+            transfer(*BOB, 4000).sign(0, &ALICES_SECRET_KEY.clone()),
+            transfer(*BURNS, 1000).sign(0, &BOBS_SECRET_KEY.clone()),
+        ])
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<Vec<i64>>(&body).unwrap().len(), 2);
+
+        let response = post_batch(vec![
+            transfer(*BOB, 1000).sign(1, &ALICES_SECRET_KEY.clone()),
+            transfer(*BURNS, 100000).sign(1, &BOBS_SECRET_KEY.clone()),
+        ])
+        .await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // The batch fails with the status of the transaction that failed
+        let response = post_batch(vec![
+            transfer(*BOB, 1000).sign(1, &ALICES_SECRET_KEY.clone()),
+            transfer(*BURNS, 1000).sign(5, &BOBS_SECRET_KEY.clone()),
+        ])
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_batch(vec![]).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_batch(
+            (0..=constants::MAX_BATCH_SIZE as i64)
+                .map(|nonce| transfer(*BOB, 1).sign(nonce + 1, &ALICES_SECRET_KEY.clone()))
+                .collect(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 6000);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 3000);
+        assert_eq!(db::get_balance(&pool, &BURNS, &usd()).await.unwrap(), 1000);
+    }
+
+    #[sqlx::test]
+    async fn test_transfer_memo(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 10000,
+            memo: Some("x".repeat(constants::MAX_MEMO_LENGTH + 1)),
+            invoice_id: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 10000,
+            memo: Some("Invoice #1234".to_string()),
+            invoice_id: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let ledger_entries = db::get_ledger_entries(&pool, &BOB, &usd(), 10)
+            .await
+            .unwrap();
+
+        assert_eq!(ledger_entries.len(), 1);
+        assert_eq!(ledger_entries[0].memo, Some("Invoice #1234".to_string()));
+    }
+
+    #[sqlx::test]
+    async fn test_replayed_transfer(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 5000,
+            memo: None,
+            invoice_id: None,
+        });
+        let signed_transaction = transaction.sign(0, &ALICES_SECRET_KEY.clone());
+
+        for expected_status in [StatusCode::OK, StatusCode::BAD_REQUEST] {
+            let response = post_transaction(&pool, &signed_transaction).await;
+
+            assert_eq!(response.status(), expected_status);
+        }
+
+        assert_eq!(db::get_nonce(&pool, &ALICE).await.unwrap(), 1);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 5000);
+    }
+
+    #[sqlx::test]
+    async fn test_check(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::CreateCheck(CreateCheck {
+            currency: usd(),
+            value: 10000,
+            signer: *CHECK_ADDRESS,
+            expires_at: None,
+            memo: None,
+        });
+
+        let signed_transaction = transaction.sign(0, &ALICES_SECRET_KEY.clone());
+        let request = Request::builder()
+            .method("POST")
+            .header("content-type", "application/octet-stream")
+            .uri("/transactions")
+            .body(Body::from(borsh::to_vec(&signed_transaction).unwrap()))
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+
+        let transaction = Transaction::CashCheck(CashCheck::sign(
+            transaction_id,
+            10000,
+            *BOB,
+            0,
+            &CHECK_SECRET_KEY,
+        ));
+
+        let signed_transaction = transaction.sign(0, &BOBS_SECRET_KEY.clone());
+        let request = Request::builder()
+            .method("POST")
+            .header("content-type", "application/octet-stream")
+            .uri("/transactions")
+            .body(Body::from(borsh::to_vec(&signed_transaction).unwrap()))
+            .unwrap();
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/balances/usd/{}", hex::encode((*BOB).0)))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<i64>(&body).unwrap(), 10000);
+    }
+
+    #[sqlx::test]
+    async fn test_check_signatures(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::CreateCheck(CreateCheck {
+            currency: usd(),
+            value: 10000,
+            signer: *CHECK_ADDRESS,
+            expires_at: None,
+            memo: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+
+        let forged_check = Transaction::CashCheck(CashCheck::sign(
+            transaction_id,
+            10000,
+            *BOB,
+            0,
+            &BOBS_SECRET_KEY,
+        ));
+        let response =
+            post_transaction(&pool, &forged_check.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let check = Transaction::CashCheck(CashCheck::sign(
+            transaction_id,
+            10000,
+            *BOB,
+            0,
+            &CHECK_SECRET_KEY,
+        ));
+        let response = post_transaction(&pool, &check.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = post_transaction(&pool, &check.sign(1, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 10000);
+    }
+
+    #[sqlx::test]
+    async fn test_partially_cashed_check(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::CreateCheck(CreateCheck {
+            currency: usd(),
+            value: 10000,
+            signer: *CHECK_ADDRESS,
+            expires_at: None,
+            memo: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+
+        let check = Transaction::CashCheck(CashCheck::sign(
+            transaction_id,
+            4000,
+            *BOB,
+            0,
+            &CHECK_SECRET_KEY,
+        ));
+        let response = post_transaction(&pool, &check.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/checks/{}", transaction_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<i64>(&body).unwrap(), 6000);
+
+        let response = post_transaction(&pool, &check.sign(1, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let check = Transaction::CashCheck(CashCheck::sign(
+            transaction_id,
+            7000,
+            *BOB,
+            4000,
+            &CHECK_SECRET_KEY,
+        ));
+        let response = post_transaction(&pool, &check.sign(1, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let void_check = Transaction::VoidCheck(VoidCheck { transaction_id });
+        let response =
+            post_transaction(&pool, &void_check.sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 6000);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 4000);
+    }
+
+    #[sqlx::test]
+    async fn test_void_check(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::CreateCheck(CreateCheck {
+            currency: usd(),
+            value: 10000,
+            signer: *CHECK_ADDRESS,
+            expires_at: None,
+            memo: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+
+        let void_check = Transaction::VoidCheck(VoidCheck { transaction_id });
+        let response = post_transaction(&pool, &void_check.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let response =
+            post_transaction(&pool, &void_check.sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let check = Transaction::CashCheck(CashCheck::sign(
+            transaction_id,
+            10000,
+            *BOB,
+            0,
+            &CHECK_SECRET_KEY,
+        ));
+        let response = post_transaction(&pool, &check.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 10000);
+    }
+
+    #[sqlx::test]
+    async fn test_expired_check(pool: PgPool) {
+        db::insert_bitcoin_block(
+            &pool,
+            bitcoin_block!("deposit-block-877380.block"),
+            HashMap::from([(usd(), 100000f64)]),
+            vec![],
+        )
+        .await
+        .unwrap();
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::CreateCheck(CreateCheck {
+            currency: usd(),
+            value: 10000,
+            signer: *CHECK_ADDRESS,
+            expires_at: Some(db::get_current_block(&pool).await.unwrap().unwrap()),
+            memo: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+
+        let check = Transaction::CashCheck(CashCheck::sign(
+            transaction_id,
+            10000,
+            *BOB,
+            0,
+            &CHECK_SECRET_KEY,
+        ));
+        let response = post_transaction(&pool, &check.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let void_check = Transaction::VoidCheck(VoidCheck { transaction_id });
+        let response =
+            post_transaction(&pool, &void_check.sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn test_claim_lock(pool: PgPool) {
+        db::insert_bitcoin_block(
+            &pool,
+            bitcoin_block!("deposit-block-877380.block"),
+            HashMap::from([(usd(), 100000f64)]),
+            vec![],
+        )
+        .await
+        .unwrap();
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let preimage = [7u8; 32];
+        let transaction = Transaction::LockTransfer(LockTransfer {
+            to: *ALICE,
+            currency: usd(),
+            value: -10000,
+            hashlock: Sha256::digest(preimage).into(),
+            timeout: db::get_current_block(&pool).await.unwrap().unwrap() + 6,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let transaction = Transaction::LockTransfer(LockTransfer {
+            to: *BOB,
+            currency: usd(),
+            value: 10000,
+            hashlock: Sha256::digest(preimage).into(),
+            timeout: db::get_current_block(&pool).await.unwrap().unwrap() + 6,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+
+        let refund_lock = Transaction::RefundLock(RefundLock { transaction_id });
+        let response =
+            post_transaction(&pool, &refund_lock.sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let claim_lock = Transaction::ClaimLock(ClaimLock {
+            transaction_id,
+            preimage: [8u8; 32],
+        });
+        let response = post_transaction(&pool, &claim_lock.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let claim_lock = Transaction::ClaimLock(ClaimLock {
+            transaction_id,
+            preimage,
+        });
+        let response = post_transaction(&pool, &claim_lock.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 10000);
+    }
+
+    #[sqlx::test]
+    async fn test_refund_lock(pool: PgPool) {
+        db::insert_bitcoin_block(
+            &pool,
+            bitcoin_block!("deposit-block-877380.block"),
+            HashMap::from([(usd(), 100000f64)]),
+            vec![],
+        )
+        .await
+        .unwrap();
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let preimage = [7u8; 32];
+        let transaction = Transaction::LockTransfer(LockTransfer {
+            to: *BOB,
+            currency: usd(),
+            value: 10000,
+            hashlock: Sha256::digest(preimage).into(),
+            timeout: db::get_current_block(&pool).await.unwrap().unwrap(),
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+
+        let claim_lock = Transaction::ClaimLock(ClaimLock {
+            transaction_id,
+            preimage,
+        });
+        let response = post_transaction(&pool, &claim_lock.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let refund_lock = Transaction::RefundLock(RefundLock { transaction_id });
+        let response =
+            post_transaction(&pool, &refund_lock.sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 10000);
+    }
+
+    #[sqlx::test]
+    async fn test_standing_order(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 25000).await.unwrap();
+        let transaction = Transaction::CreateStandingOrder(CreateStandingOrder {
+            to: *BOB,
+            currency: usd(),
+            value: 10000,
+            interval: 1,
+            max_occurrences: 4,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id = from_slice::<i64>(&body).unwrap();
+
+        for _ in 0..3 {
+            sqlx::query("INSERT into blocks DEFAULT VALUES")
+                .execute(&pool)
+                .await
+                .unwrap();
+            db::execute_standing_orders(&pool).await.unwrap();
+        }
+        db::execute_standing_orders(&pool).await.unwrap();
+
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 20000);
+        let history = db::get_history(
+            &pool,
+            &BOB,
+            db::HistoryFilter {
+                cursor: None,
+                limit: 10,
+                currency: None,
+                direction: None,
+                from_block: None,
+                to_block: None,
+                from_time: None,
+                to_time: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(history.entries.len(), 2);
+        assert!(history
+            .entries
+            .iter()
+            .all(|entry| entry.transaction_id == transaction_id
+                && entry.standing_order_run.is_some()));
+        assert_ne!(
+            history.entries[0].standing_order_run,
+            history.entries[1].standing_order_run
+        );
+        assert_ne!(
+            history.entries[0].block_height,
+            history.entries[1].block_height
+        );
+        assert_eq!(
+            sqlx::query("SELECT reason FROM standing_order_failures")
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            sqlx::query_scalar::<_, i64>("SELECT remaining_occurrences FROM standing_orders")
+                .fetch_one(&pool)
+                .await
+                .unwrap(),
+            1
+        );
+
+        let cancel_standing_order =
+            Transaction::CancelStandingOrder(CancelStandingOrder { transaction_id });
+        let response = post_transaction(
+            &pool,
+            &cancel_standing_order.sign(1, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        sqlx::query("INSERT into blocks DEFAULT VALUES")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(db::execute_standing_orders(&pool).await.unwrap().len(), 0);
+    }
+
+    #[sqlx::test]
+    async fn test_transfer_from(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::Approve(Approve {
+            spender: *BOB,
+            currency: usd(),
+            limit: 6000,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let transaction = Transaction::TransferFrom(TransferFrom {
+            owner: *ALICE,
+            to: *BURNS,
+            currency: usd(),
+            value: 4000,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response =
+            post_transaction(&pool, &transaction.sign(1, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let transaction = Transaction::TransferFrom(TransferFrom {
+            owner: *ALICE,
+            to: *BURNS,
+            currency: usd(),
+            value: -4000,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(1, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/allowances/usd/{}/{}",
+                hex::encode((*ALICE).0),
+                hex::encode((*BOB).0)
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<i64>(&body).unwrap(), 2000);
+        assert_eq!(db::get_balance(&pool, &BURNS, &usd()).await.unwrap(), 4000);
+    }
+
+    #[sqlx::test]
+    async fn test_multi_sig_transfer(pool: PgPool) {
+        let policy = MultiSigPolicy {
+            threshold: 2,
+            public_keys: [&*ALICES_SECRET_KEY, &*BOBS_SECRET_KEY, &*BURNS_SECRET_KEY]
+                .iter()
+                .map(|signing_key| {
+                    VerifyingKey::from(*signing_key)
+                        .to_sec1_bytes()
+                        .as_ref()
+                        .try_into()
+                        .unwrap()
+                })
+                .collect(),
+        };
+        let treasury = Address::from(&policy);
+        db::credit(&pool, treasury, usd(), 10000).await.unwrap();
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
             to: crate::transaction::Address::Stable(*BOB),
             value: 10000,
+            memo: None,
+            invoice_id: None,
+        });
+        let response = post_transaction(
+            &pool,
+            &transaction.sign_multi_sig(
+                0,
+                policy.clone(),
+                &[&ALICES_SECRET_KEY, &ALICES_SECRET_KEY, &CHECK_SECRET_KEY],
+            ),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_transaction(
+            &pool,
+            &transaction.sign_multi_sig(0, policy, &[&ALICES_SECRET_KEY, &BURNS_SECRET_KEY]),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 10000);
+    }
+
+    #[sqlx::test]
+    async fn test_rotate_key(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        db::credit(&pool, *BOB, usd(), 10000).await.unwrap();
+        let transaction = Transaction::RotateKey(RotateKey {
+            public_key: VerifyingKey::from(BOBS_SECRET_KEY.clone())
+                .to_sec1_bytes()
+                .as_ref()
+                .try_into()
+                .unwrap(),
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let new_secret_key = SigningKey::random(&mut OsRng);
+        let transaction = Transaction::RotateKey(RotateKey {
+            public_key: VerifyingKey::from(new_secret_key.clone())
+                .to_sec1_bytes()
+                .as_ref()
+                .try_into()
+                .unwrap(),
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 4000,
+            memo: None,
+            invoice_id: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_transaction(&pool, &transaction.sign(1, &new_secret_key)).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 6000);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 14000);
+    }
+
+    #[sqlx::test]
+    async fn test_session_key(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        sqlx::query(
+            "INSERT into fees (transaction_type, currency, flat, basis_points) VALUES ('transfer', 'usd', 10, 0)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let session_key = SigningKey::random(&mut OsRng);
+        let authorize_session_key = |limit, key: &SigningKey| {
+            AuthorizeSessionKey::sign(*ALICE, usd(), limit, 10, vec![*BOB], key)
+        };
+        let mut unsigned_authorization =
+            authorize_session_key(5000, &SigningKey::random(&mut OsRng));
+        unsigned_authorization.public_key = authorize_session_key(5000, &session_key).public_key;
+        let response = post_transaction(
+            &pool,
+            &Transaction::AuthorizeSessionKey(unsigned_authorization)
+                .sign(0, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let transaction =
+            Transaction::AuthorizeSessionKey(authorize_session_key(5000, &session_key));
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let transfer = |to, value| {
+            Transaction::Transfer(Transfer {
+                currency: usd(),
+                to: crate::transaction::Address::Stable(to),
+                value,
+                memo: None,
+                invoice_id: None,
+            })
+        };
+        let response = post_transaction(&pool, &transfer(*BOB, 3000).sign(1, &session_key)).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = post_transaction(&pool, &transfer(*BURNS, 1000).sign(2, &session_key)).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // The fee on this transfer would take it over the limit
+        let response = post_transaction(&pool, &transfer(*BOB, 1990).sign(2, &session_key)).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let transaction = Transaction::Approve(Approve {
+            spender: *BURNS,
+            currency: usd(),
+            limit: 1000,
+        });
+        let response = post_transaction(&pool, &transaction.sign(2, &session_key)).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let response = post_transaction(&pool, &transfer(*BOB, 1980).sign(2, &session_key)).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 5000);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 4980);
+
+        let transaction =
+            Transaction::AuthorizeSessionKey(authorize_session_key(5000, &BOBS_SECRET_KEY.clone()));
+        let response =
+            post_transaction(&pool, &transaction.sign(3, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let transaction = Transaction::AuthorizeSessionKey(authorize_session_key(0, &session_key));
+        let response =
+            post_transaction(&pool, &transaction.sign(3, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM session_keys")
+                .fetch_one(&pool)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_recovery(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let new_secret_key = SigningKey::random(&mut OsRng);
+        let transaction = Transaction::SetRecoveryPolicy(SetRecoveryPolicy {
+            guardians: vec![*BOB, *BURNS],
+            threshold: 2,
+            delay: 2,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let initiate_recovery = Transaction::InitiateRecovery(InitiateRecovery {
+            account: *ALICE,
+            public_key: VerifyingKey::from(new_secret_key.clone())
+                .to_sec1_bytes()
+                .as_ref()
+                .try_into()
+                .unwrap(),
+        });
+        let response =
+            post_transaction(&pool, &initiate_recovery.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let cancelled_recovery_id: i64 =
+            borsh::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+        let transaction = Transaction::CancelRecovery(CancelRecovery {
+            transaction_id: cancelled_recovery_id,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let transaction = Transaction::ApproveRecovery(ApproveRecovery {
+            transaction_id: cancelled_recovery_id,
         });
-        let _transaction2 = Transaction::Transfer(Transfer {
-            currency: Currency::Usd,
-            to: transaction::Address::Bitcoin("36sTjLr6VTRfF5MQGTH3BVVeDH17aEwQQW".to_string()),
-            value: 4,
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &BURNS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let response =
+            post_transaction(&pool, &initiate_recovery.sign(1, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let recovery_id: i64 =
+            borsh::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+        let finalize_recovery = Transaction::FinalizeRecovery(FinalizeRecovery {
+            transaction_id: recovery_id,
         });
-        // println!("{}", hex::encode(borsh::to_vec(&(2i64, transaction2)).unwrap()));
+        let response =
+            post_transaction(&pool, &finalize_recovery.sign(2, &BOBS_SECRET_KEY.clone())).await;
 
-        let signed_transaction = transaction.sign(0, &ALICES_SECRET_KEY.clone());
-        let request = Request::builder()
-            .method("POST")
-            .header("content-type", "application/octet-stream")
-            .uri("/transactions")
-            .body(Body::from(borsh::to_vec(&signed_transaction).unwrap()))
-            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
 
-        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+        let transaction = Transaction::ApproveRecovery(ApproveRecovery {
+            transaction_id: recovery_id,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &BURNS_SECRET_KEY.clone())).await;
 
         assert_eq!(response.status(), StatusCode::OK);
 
-        let request = Request::builder()
-            .method("GET")
-            .uri(format!("/balances/usd/{}", hex::encode((*ALICE).0)))
-            .body(Body::empty())
-            .unwrap();
+        let response =
+            post_transaction(&pool, &finalize_recovery.sign(2, &BOBS_SECRET_KEY.clone())).await;
 
-        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        for _ in 0..2 {
+            sqlx::query("INSERT into blocks DEFAULT VALUES")
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let response =
+            post_transaction(&pool, &finalize_recovery.sign(2, &BOBS_SECRET_KEY.clone())).await;
 
         assert_eq!(response.status(), StatusCode::OK);
 
-        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 4000,
+            memo: None,
+            invoice_id: None,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(2, &ALICES_SECRET_KEY.clone())).await;
 
-        assert_eq!(from_slice::<i64>(&body).unwrap(), 0);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-        let request = Request::builder()
-            .method("GET")
-            .uri(format!("/balances/usd/{}", hex::encode((*BOB).0)))
-            .body(Body::empty())
-            .unwrap();
+        let response = post_transaction(&pool, &transaction.sign(2, &new_secret_key)).await;
 
-        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 6000);
+
+        let initiate_recovery = Transaction::InitiateRecovery(InitiateRecovery {
+            account: *ALICE,
+            public_key: VerifyingKey::from(BURNS_SECRET_KEY.clone())
+                .to_sec1_bytes()
+                .as_ref()
+                .try_into()
+                .unwrap(),
+        });
+        let response =
+            post_transaction(&pool, &initiate_recovery.sign(3, &BOBS_SECRET_KEY.clone())).await;
 
         assert_eq!(response.status(), StatusCode::OK);
+        let recovery_id: i64 =
+            borsh::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
 
-        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction = Transaction::ApproveRecovery(ApproveRecovery {
+            transaction_id: recovery_id,
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(1, &BURNS_SECRET_KEY.clone())).await;
 
-        assert_eq!(from_slice::<i64>(&body).unwrap(), 10000);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for _ in 0..2 {
+            sqlx::query("INSERT into blocks DEFAULT VALUES")
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let finalize_recovery = Transaction::FinalizeRecovery(FinalizeRecovery {
+            transaction_id: recovery_id,
+        });
+        let response =
+            post_transaction(&pool, &finalize_recovery.sign(4, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
     #[sqlx::test]
-    async fn test_check(pool: PgPool) {
-        db::credit(&pool, *ALICE, Currency::Usd, 10000)
-            .await
-            .unwrap();
-        let transaction = Transaction::CreateCheck(CreateCheck {
-            currency: Currency::Usd,
-            value: 10000,
-            signer: *CHECK_ADDRESS,
+    async fn sponsored_withdraw(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        db::credit(&pool, *BOB, usd(), 100).await.unwrap();
+        db::insert_bitcoin_block(
+            &pool,
+            bitcoin_block!("deposit-block-877380.block"),
+            HashMap::from([(usd(), 100000f64)]),
+            vec![],
+        )
+        .await
+        .unwrap();
+        let satoshis = db::currency_to_satoshis(&pool, &usd(), 6000).await.unwrap();
+        let bitcoin_rpc_mock = BITCOIND.mock(|when, then| {
+            when.method("POST")
+                .body(
+                    json!({
+                        "jsonrpc": "1.0",
+                        "method": "sendtoaddress",
+                        "params": [
+                            ALICES_BITCOIN_ADDRESS.to_string(),
+                            Decimal::new(satoshis, 8)
+                        ]
+                    })
+                    .to_string(),
+                )
+                .path("/");
+            then.status(200)
+                .header("content-type", "text/json; charset=UTF-8")
+                .body(
+                    json!({
+                        "error": null,
+                        "result": "0000000000000000000000000000000000000000000000000000000000000000"
+                    })
+                    .to_string(),
+                );
         });
-
-        let signed_transaction = transaction.sign(0, &ALICES_SECRET_KEY.clone());
-        let request = Request::builder()
-            .method("POST")
-            .header("content-type", "application/octet-stream")
-            .uri("/transactions")
-            .body(Body::from(borsh::to_vec(&signed_transaction).unwrap()))
+        BITCOIND.mock(|when, then| {
+            when.method("POST").body_includes("estimatesmartfee");
+            then.status(200)
+                .header("content-type", "text/json; charset=UTF-8")
+                .body(
+                    json!({"error": null, "result": {"feerate": 0.00002, "blocks": 6}}).to_string(),
+                );
+        });
+        let miner_cost = db::satoshis_to_currency(&pool, &usd(), 2 * constants::WITHDRAWAL_VSIZE)
+            .await
             .unwrap();
+        let signed_transaction = Transaction::Transfer(Transfer {
+            to: transaction::Address::Bitcoin((*ALICES_BITCOIN_ADDRESS).to_string()),
+            currency: usd(),
+            value: 6000,
+            memo: None,
+            invoice_id: None,
+        })
+        .sign(0, &ALICES_SECRET_KEY.clone());
+        let post_sponsored_transaction = |miner_fee| {
+            let sponsored_transaction = SponsoredTransaction {
+                sponsorship: Sponsorship::sign(
+                    &signed_transaction,
+                    usd(),
+                    miner_fee,
+                    &BOBS_SECRET_KEY.clone(),
+                ),
+                signed_transaction: signed_transaction.clone(),
+            };
+            let request = Request::builder()
+                .method("POST")
+                .header("content-type", "application/octet-stream")
+                .uri("/transactions/sponsored")
+                .body(Body::from(borsh::to_vec(&sponsored_transaction).unwrap()))
+                .unwrap();
+            let pool = pool.clone();
+            async move { app(pool).await.oneshot(request).await.unwrap() }
+        };
 
-        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
-        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response = post_sponsored_transaction(miner_cost - 1).await;
 
-        let transaction_id = from_slice::<i64>(&body).unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
 
-        let transaction =
-            Transaction::CashCheck(CashCheck::sign(transaction_id, *BOB, &CHECK_SECRET_KEY));
+        let response = post_sponsored_transaction(miner_cost).await;
 
-        let signed_transaction = transaction.sign(0, &BOBS_SECRET_KEY.clone());
+        assert_eq!(response.status(), StatusCode::OK);
+        bitcoin_rpc_mock.assert();
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 4000);
+        assert_eq!(
+            db::get_balance(&pool, &BOB, &usd()).await.unwrap(),
+            100 - miner_cost
+        );
+        assert_eq!(
+            sqlx::query_scalar::<_, i64>("SELECT miner_fee FROM sponsorships")
+                .fetch_one(&pool)
+                .await
+                .unwrap(),
+            miner_cost
+        );
+
+        let signed_transaction = Transaction::Transfer(Transfer {
+            to: transaction::Address::Stable(*BURNS),
+            currency: usd(),
+            value: 4000,
+            memo: None,
+            invoice_id: None,
+        })
+        .sign(1, &ALICES_SECRET_KEY.clone());
+        let sponsored_transaction = SponsoredTransaction {
+            sponsorship: Sponsorship::sign(&signed_transaction, usd(), 5, &BOBS_SECRET_KEY.clone()),
+            signed_transaction,
+        };
         let request = Request::builder()
             .method("POST")
             .header("content-type", "application/octet-stream")
-            .uri("/transactions")
-            .body(Body::from(borsh::to_vec(&signed_transaction).unwrap()))
-            .unwrap();
-        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
-
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let request = Request::builder()
-            .method("GET")
-            .uri(format!("/balances/usd/{}", hex::encode((*BOB).0)))
-            .body(Body::empty())
+            .uri("/transactions/sponsored")
+            .body(Body::from(borsh::to_vec(&sponsored_transaction).unwrap()))
             .unwrap();
 
         let response = app(pool.clone()).await.oneshot(request).await.unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = response.into_body().collect().await.unwrap().to_bytes();
-
-        assert_eq!(from_slice::<i64>(&body).unwrap(), 10000);
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 
     #[sqlx::test]
     async fn withdraw(pool: PgPool) {
-        let server = MockServer::start();
         let bitcoin_rpc_mock =
-            server.mock(|when, then| {
+            BITCOIND.mock(|when, then| {
                 when.method("POST")
                     .body(
                         json!({
@@ -602,14 +2712,11 @@ mod tests {
                     "result": "0000000000000000000000000000000000000000000000000000000000000000"
                 }).to_string());
             });
-        env::set_var("BITCOIND_URL", server.url(""));
-        db::credit(&pool, *ALICE, Currency::Usd, 10000)
-            .await
-            .unwrap();
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
         db::insert_bitcoin_block(
             &pool,
             bitcoin_block!("deposit-block-877380.block"),
-            HashMap::from([(Currency::Usd, 100000f64)]),
+            HashMap::from([(usd(), 100000f64)]),
             vec![(
                 db::Utxo {
                     transaction_id: TEST_UTXO.0.to_vec(),
@@ -623,8 +2730,10 @@ mod tests {
         .unwrap();
         let transaction = Transaction::Transfer(Transfer {
             to: transaction::Address::Bitcoin((*ALICES_BITCOIN_ADDRESS).to_string()),
-            currency: Currency::Usd,
+            currency: usd(),
             value: 10000,
+            memo: None,
+            invoice_id: None,
         });
         let signed_transaction = transaction.sign(0, &ALICES_SECRET_KEY.clone());
         let request = Request::builder()
@@ -653,13 +2762,129 @@ mod tests {
 
         assert_eq!(from_slice::<i64>(&body).unwrap(), 0);
     }
+    #[sqlx::test]
+    async fn test_batch_transfer(pool: PgPool) {
+        let bitcoin_rpc_mock = BITCOIND.mock(|when, then| {
+            when.method("POST")
+                .body(
+                    json!({
+                        "jsonrpc": "1.0",
+                        "method": "sendtoaddress",
+                        "params": [
+                            ALICES_BITCOIN_ADDRESS.to_string(),
+                            Decimal::new(49995, 8)
+                        ]
+                    })
+                    .to_string(),
+                )
+                .path("/");
+            then.status(200)
+                .header("content-type", "text/json; charset=UTF-8")
+                .body(
+                    json!({
+                        "error": null,
+                        "result": "0000000000000000000000000000000000000000000000000000000000000000"
+                    })
+                    .to_string(),
+                );
+        });
+        db::insert_bitcoin_block(
+            &pool,
+            bitcoin_block!("deposit-block-877380.block"),
+            HashMap::from([(usd(), 100000f64)]),
+            vec![],
+        )
+        .await
+        .unwrap();
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::BatchTransfer(BatchTransfer {
+            transfers: vec![
+                Transfer {
+                    currency: usd(),
+                    to: transaction::Address::Stable(*BOB),
+                    value: 3000,
+                    memo: None,
+                    invoice_id: None,
+                },
+                Transfer {
+                    currency: usd(),
+                    to: transaction::Address::Stable(*BURNS),
+                    value: 2000,
+                    memo: None,
+                    invoice_id: None,
+                },
+                Transfer {
+                    currency: usd(),
+                    to: transaction::Address::Bitcoin((*ALICES_BITCOIN_ADDRESS).to_string()),
+                    value: 5000,
+                    memo: None,
+                    invoice_id: None,
+                },
+            ],
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        bitcoin_rpc_mock.assert();
+
+        let transaction = Transaction::BatchTransfer(BatchTransfer {
+            transfers: vec![
+                Transfer {
+                    currency: usd(),
+                    to: transaction::Address::Stable(*ALICE),
+                    value: 2000,
+                    memo: None,
+                    invoice_id: None,
+                },
+                Transfer {
+                    currency: usd(),
+                    to: transaction::Address::Stable(*BURNS),
+                    value: 2000,
+                    memo: None,
+                    invoice_id: None,
+                },
+            ],
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let transaction = Transaction::BatchTransfer(BatchTransfer {
+            transfers: vec![
+                Transfer {
+                    currency: usd(),
+                    to: transaction::Address::Stable(*ALICE),
+                    value: -1000,
+                    memo: None,
+                    invoice_id: None,
+                },
+                Transfer {
+                    currency: usd(),
+                    to: transaction::Address::Stable(*BURNS),
+                    value: 3500,
+                    memo: None,
+                    invoice_id: None,
+                },
+            ],
+        });
+        let response =
+            post_transaction(&pool, &transaction.sign(0, &BOBS_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 0);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 3000);
+        assert_eq!(db::get_balance(&pool, &BURNS, &usd()).await.unwrap(), 2000);
+    }
+
     #[sqlx::test]
     async fn claim_utxo2(pool: PgPool) {
         let block = bitcoin_block!("deposit-block-877380.block");
         db::insert_bitcoin_block(
             &pool,
             block,
-            HashMap::from([(Currency::Usd, 100000f64)]),
+            HashMap::from([(usd(), 100000f64)]),
             vec![(
                 db::Utxo {
                     transaction_id: TEST_UTXO.0.to_vec(),
@@ -674,7 +2899,7 @@ mod tests {
         let transaction = Transaction::ClaimUtxo(transaction::ClaimUtxo {
             transaction_id: TEST_UTXO.0,
             vout: TEST_UTXO.1,
-            currency: Currency::Usd,
+            currency: usd(),
         });
         let signed_transaction = transaction.sign(0, &BURNS_SECRET_KEY.clone());
         let request = Request::builder()
@@ -698,4 +2923,477 @@ mod tests {
 
         assert_eq!(from_slice::<i64>(&body).unwrap(), 100);
     }
+
+    #[sqlx::test]
+    async fn test_simulate_transaction(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let simulate = |value| {
+            let pool = pool.clone();
+            async move {
+                let transaction = Transaction::Transfer(Transfer {
+                    currency: usd(),
+                    to: crate::transaction::Address::Stable(*BOB),
+                    value,
+                    memo: None,
+                    invoice_id: None,
+                });
+                let request = Request::builder()
+                    .method("POST")
+                    .header("content-type", "application/octet-stream")
+                    .uri("/transactions/simulate")
+                    .body(Body::from(
+                        borsh::to_vec(&transaction.sign(0, &ALICES_SECRET_KEY.clone())).unwrap(),
+                    ))
+                    .unwrap();
+                let response = app(pool).await.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                from_slice::<db::Simulation>(&body).unwrap()
+            }
+        };
+
+        let simulation = simulate(4000).await;
+
+        assert_eq!(simulation.error, None);
+        assert_eq!(simulation.fees, vec![]);
+        assert_eq!(
+            simulation.balance_deltas,
+            vec![
+                db::BalanceDelta {
+                    address: *ALICE,
+                    currency: usd(),
+                    value: -4000,
+                },
+                db::BalanceDelta {
+                    address: *BOB,
+                    currency: usd(),
+                    value: 4000,
+                },
+            ]
+        );
+        assert_eq!(db::get_balance(&pool, &ALICE, &usd()).await.unwrap(), 10000);
+        assert_eq!(db::get_balance(&pool, &BOB, &usd()).await.unwrap(), 0);
+        assert!(simulate(20000).await.error.is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_get_transaction(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 4000,
+            memo: Some("rent".to_string()),
+            invoice_id: None,
+        });
+        let signed_transaction = transaction.sign(0, &ALICES_SECRET_KEY.clone());
+        let response = post_transaction(&pool, &signed_transaction).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id: i64 = from_slice(&body).unwrap();
+        let get = |uri: String| {
+            let pool = pool.clone();
+            async move {
+                let request = Request::builder()
+                    .method("GET")
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app(pool).await.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                response.into_body().collect().await.unwrap().to_bytes()
+            }
+        };
+
+        let transaction_details: db::TransactionDetails =
+            from_slice(&get(format!("/transactions/{}", transaction_id)).await).unwrap();
+
+        assert_eq!(transaction_details.signed_transaction, signed_transaction);
+        assert_eq!(transaction_details.signer, *ALICE);
+        assert_eq!(transaction_details.status, db::TransactionStatus::Settled);
+        assert_eq!(transaction_details.ledger_entries.len(), 1);
+        assert_eq!(transaction_details.ledger_entries[0].recipient, BOB.0);
+        assert_eq!(transaction_details.ledger_entries[0].value, 4000);
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&get(format!("/transactions/{}/json", transaction_id)).await)
+                .unwrap();
+
+        assert_eq!(json["signer"], hex::encode(ALICE.0));
+        assert_eq!(json["status"], "settled");
+        assert_eq!(
+            json["transaction"]["Transfer"]["to"]["Stable"],
+            hex::encode(BOB.0)
+        );
+        assert_eq!(json["transaction"]["Transfer"]["currency"], "usd");
+        assert_eq!(json["ledger_entries"][0]["memo"], "rent");
+
+        let session_key = SigningKey::random(&mut OsRng);
+        let transaction = Transaction::AuthorizeSessionKey(AuthorizeSessionKey::sign(
+            *ALICE,
+            usd(),
+            5000,
+            10,
+            vec![],
+            &session_key,
+        ));
+        post_transaction(&pool, &transaction.sign(1, &ALICES_SECRET_KEY.clone())).await;
+        let transaction = Transaction::Transfer(Transfer {
+            currency: usd(),
+            to: crate::transaction::Address::Stable(*BOB),
+            value: 1000,
+            memo: None,
+            invoice_id: None,
+        });
+        let response = post_transaction(&pool, &transaction.sign(2, &session_key)).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let transaction_id: i64 = from_slice(&body).unwrap();
+        let transaction_details: db::TransactionDetails =
+            from_slice(&get(format!("/transactions/{}", transaction_id)).await).unwrap();
+
+        assert_eq!(transaction_details.signer, *ALICE);
+
+        let legacy_transaction = legacy::Transaction::Transfer(legacy::Transfer {
+            currency: legacy::Currency::Usd,
+            to: legacy::Address::Stable(*ALICE),
+            value: 1000,
+        });
+        let (signature, recovery_id) = BOBS_SECRET_KEY
+            .sign_recoverable(&borsh::to_vec(&(7i64, &legacy_transaction)).unwrap())
+            .unwrap();
+        let legacy_transaction = legacy::SignedTransaction {
+            transaction: legacy_transaction,
+            nonce: 7,
+            signature: [signature.to_bytes().as_slice(), &[recovery_id.to_byte()]]
+                .concat()
+                .try_into()
+                .unwrap(),
+        };
+        let transaction_id: i64 =
+            sqlx::query_scalar("INSERT into transactions (data) VALUES ($1) RETURNING id")
+                .bind(borsh::to_vec(&legacy_transaction).unwrap())
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let transaction_details: db::TransactionDetails =
+            from_slice(&get(format!("/transactions/{}", transaction_id)).await).unwrap();
+
+        assert_eq!(
+            transaction_details.signed_transaction.transaction,
+            Transaction::Transfer(Transfer {
+                currency: usd(),
+                to: crate::transaction::Address::Stable(*ALICE),
+                value: 1000,
+                memo: None,
+                invoice_id: None,
+            })
+        );
+        assert_eq!(transaction_details.signed_transaction.nonce, 7);
+        assert_eq!(transaction_details.signer, *BOB);
+
+        let legacy_transaction = legacy::Transaction::CashCheck(legacy::CashCheck {
+            transaction_id: 1,
+            signature: [1; 65],
+        });
+        let (signature, recovery_id) = BOBS_SECRET_KEY
+            .sign_recoverable(&borsh::to_vec(&(8i64, &legacy_transaction)).unwrap())
+            .unwrap();
+        let legacy_transaction = legacy::SignedTransaction {
+            transaction: legacy_transaction,
+            nonce: 8,
+            signature: [signature.to_bytes().as_slice(), &[recovery_id.to_byte()]]
+                .concat()
+                .try_into()
+                .unwrap(),
+        };
+        let transaction_id: i64 =
+            sqlx::query_scalar("INSERT into transactions (data) VALUES ($1) RETURNING id")
+                .bind(borsh::to_vec(&legacy_transaction).unwrap())
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let transaction_details: db::TransactionDetails =
+            from_slice(&get(format!("/transactions/{}", transaction_id)).await).unwrap();
+
+        assert_eq!(
+            transaction_details.signed_transaction.transaction,
+            Transaction::CashCheck(CashCheck {
+                transaction_id: 1,
+                value: 0,
+                signature: [1; 65],
+            })
+        );
+        assert_eq!(transaction_details.signer, *BOB);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri("/transactions/1000000")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn test_history(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transfer = |to, value, memo: Option<&str>| {
+            Transaction::Transfer(Transfer {
+                currency: usd(),
+                to: crate::transaction::Address::Stable(to),
+                value,
+                memo: memo.map(str::to_string),
+                invoice_id: None,
+            })
+        };
+        post_transaction(
+            &pool,
+            &transfer(*BOB, 4000, Some("rent")).sign(0, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+        post_transaction(
+            &pool,
+            &transfer(*ALICE, 1000, None).sign(0, &BOBS_SECRET_KEY.clone()),
+        )
+        .await;
+        post_transaction(
+            &pool,
+            &transfer(*BOB, 500, None).sign(1, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+        let get_history = |query: String| {
+            let pool = pool.clone();
+            async move {
+                let request = Request::builder()
+                    .method("GET")
+                    .uri(format!("/history/{}?{}", hex::encode(ALICE.0), query))
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app(pool).await.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                from_slice::<db::History>(&body).unwrap()
+            }
+        };
+
+        let history = get_history("limit=2".to_string()).await;
+
+        assert_eq!(
+            history
+                .entries
+                .iter()
+                .map(|entry| (entry.value, &entry.direction))
+                .collect::<Vec<_>>(),
+            vec![
+                (500, &db::Direction::Outgoing),
+                (1000, &db::Direction::Incoming)
+            ]
+        );
+        assert_eq!(history.entries[1].counterparty, *BOB);
+        assert_eq!(history.entries[1].transaction_type, "transfer");
+        assert!(history.entries[1].timestamp.is_some());
+
+        let history = get_history(format!("limit=2&cursor={}", history.next_cursor.unwrap())).await;
+
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].value, 4000);
+        assert_eq!(history.entries[0].memo, Some("rent".to_string()));
+        assert_eq!(history.next_cursor, None);
+
+        let history = get_history("direction=outgoing&currency=usd".to_string()).await;
+
+        assert_eq!(
+            history
+                .entries
+                .iter()
+                .map(|entry| entry.value)
+                .collect::<Vec<_>>(),
+            vec![500, 4000]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_statement(pool: PgPool) {
+        db::credit(&pool, *ALICE, usd(), 10000).await.unwrap();
+        let transfer = |to, value, memo: Option<&str>| {
+            Transaction::Transfer(Transfer {
+                currency: usd(),
+                to: crate::transaction::Address::Stable(to),
+                value,
+                memo: memo.map(str::to_string),
+                invoice_id: None,
+            })
+        };
+        post_transaction(
+            &pool,
+            &transfer(*BOB, 4000, Some("rent, october")).sign(0, &ALICES_SECRET_KEY.clone()),
+        )
+        .await;
+        post_transaction(
+            &pool,
+            &transfer(*ALICE, 1000, None).sign(0, &BOBS_SECRET_KEY.clone()),
+        )
+        .await;
+        let get_statement = |query: &str| {
+            let request = Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/statements/usd/{}?{}",
+                    hex::encode(ALICE.0),
+                    query
+                ))
+                .body(Body::empty())
+                .unwrap();
+            let pool = pool.clone();
+            async move {
+                let response = app(pool).await.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                String::from_utf8(body.to_vec()).unwrap()
+            }
+        };
+
+        let json: serde_json::Value =
+            serde_json::from_str(&get_statement("from=1970-01-01&to=2100-01-01").await).unwrap();
+
+        assert_eq!(json["opening_balance"], 10000);
+        assert_eq!(json["closing_balance"], 7000);
+        assert_eq!(json["entries"][0]["value"], -4000);
+        assert_eq!(json["entries"][0]["counterparty"], hex::encode(BOB.0));
+        assert_eq!(json["entries"][1]["value"], 1000);
+
+        let csv = get_statement("from=1970-01-01&to=2100-01-01&format=csv").await;
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1], "1970-01-01,,,opening_balance,,,100.00,");
+        assert!(lines[2].ends_with(",-40.00,60.00,\"rent, october\""));
+        assert_eq!(lines[4], "2100-01-01,,,closing_balance,,,70.00,");
+
+        let ofx = get_statement("from=1970-01-01&to=2100-01-01&format=ofx").await;
+
+        assert!(ofx.contains("<TRNTYPE>DEBIT</TRNTYPE>"));
+        assert!(ofx.contains("<TRNAMT>-40.00</TRNAMT>"));
+        assert!(
+            ofx.contains("<LEDGERBAL><BALAMT>70.00</BALAMT><DTASOF>21000101</DTASOF></LEDGERBAL>")
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&get_statement("from=2100-01-01&to=2100-02-01").await).unwrap();
+
+        assert_eq!(json["opening_balance"], 7000);
+        assert_eq!(json["closing_balance"], 7000);
+        assert_eq!(json["entries"], json!([]));
+
+        for query in [
+            "from=2100-13-01&to=2100-02-01",
+            "from=2100-01-01&to=tomorrow",
+            "from=2100-01-01&to=2100-02-01&format=pdf",
+        ] {
+            let request = Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/statements/usd/{}?{}",
+                    hex::encode(ALICE.0),
+                    query
+                ))
+                .body(Body::empty())
+                .unwrap();
+            let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_currency_registry(pool: PgPool) {
+        let eur = Currency::from_str("EUR").unwrap();
+
+        assert_eq!(
+            from_slice::<Currency>(&borsh::to_vec("eur").unwrap()).unwrap(),
+            eur
+        );
+        assert!(from_slice::<Currency>(&borsh::to_vec("e ur").unwrap()).is_err());
+
+        sqlx::query("INSERT INTO currencies (code, decimals) VALUES ('eur', 2)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        db::insert_bitcoin_block(
+            &pool,
+            bitcoin_block!("deposit-block-877380.block"),
+            HashMap::from([(usd(), 100000f64), (eur.clone(), 90000f64)]),
+            vec![],
+        )
+        .await
+        .unwrap();
+        db::credit(&pool, *ALICE, eur.clone(), 10000).await.unwrap();
+        let transfer = |currency: &Currency| {
+            Transaction::Transfer(Transfer {
+                currency: currency.clone(),
+                to: crate::transaction::Address::Stable(*BOB),
+                value: 4000,
+                memo: None,
+                invoice_id: None,
+            })
+        };
+        let response =
+            post_transaction(&pool, &transfer(&eur).sign(0, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/balances/eur/{}", hex::encode((*BOB).0)))
+            .body(Body::empty())
+            .unwrap();
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(from_slice::<i64>(&body).unwrap(), 4000);
+        assert_eq!(
+            db::currency_to_satoshis(&pool, &eur, 9000).await.unwrap(),
+            99994
+        );
+
+        let request = Request::builder()
+            .method("GET")
+            .uri("/currencies")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(pool.clone()).await.oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(
+            from_slice::<Vec<db::CurrencyInfo>>(&body).unwrap(),
+            vec![
+                db::CurrencyInfo {
+                    currency: eur.clone(),
+                    decimals: 2,
+                    enabled: true,
+                },
+                db::CurrencyInfo {
+                    currency: usd(),
+                    decimals: 2,
+                    enabled: true,
+                },
+            ]
+        );
+
+        sqlx::query("UPDATE currencies SET enabled = false WHERE code = 'eur'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let response =
+            post_transaction(&pool, &transfer(&eur).sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let gbp = Currency::from_str("gbp").unwrap();
+        let response =
+            post_transaction(&pool, &transfer(&gbp).sign(1, &ALICES_SECRET_KEY.clone())).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(Currency::from_str("u$d").is_err());
+    }
 }
@@ -0,0 +1,217 @@
+use crate::{
+    error::{Error, Result},
+    transaction::Currency,
+    Address,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{fmt::Write, str::FromStr};
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct StatementEntry {
+    pub ledger_id: i64,
+    pub transaction_id: i64,
+    pub transaction_type: String,
+    // RFC 3339 timestamp in UTC, unknown for transactions stored before they
+    // were recorded
+    pub posted_at: Option<String>,
+    pub counterparty: Address,
+    // Negative for debits
+    pub value: i64,
+    pub memo: Option<String>,
+    pub standing_order_run: Option<i64>,
+}
+
+// A statement covers every ledger entry for an address and currency from the
+// start of `from` up to but not including `to`
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct Statement {
+    pub address: Address,
+    pub currency: Currency,
+    pub decimals: u32,
+    pub from: String,
+    pub to: String,
+    pub opening_balance: i64,
+    pub closing_balance: i64,
+    pub entries: Vec<StatementEntry>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Format {
+    Csv,
+    Ofx,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "csv" => Ok(Self::Csv),
+            "ofx" => Ok(Self::Ofx),
+            "json" => Ok(Self::Json),
+            _ => Err(Error::InvalidStatementError(format!(
+                "Unknown statement format {}",
+                input
+            ))),
+        }
+    }
+}
+
+// Statement dates are in YYYY-MM-DD form
+pub fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| Error::InvalidStatementError(format!("Invalid date {}", date)))
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Ofx => "application/x-ofx",
+            Self::Json => "application/json",
+        }
+    }
+}
+
+impl Statement {
+    pub fn render(&self, format: Format) -> Result<String> {
+        match format {
+            Format::Csv => Ok(self.to_csv()),
+            Format::Ofx => Ok(self.to_ofx()),
+            Format::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    fn amount(&self, value: i64) -> Decimal {
+        Decimal::new(value, self.decimals)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "date,transaction_id,standing_order_run,type,counterparty,amount,balance,memo\n",
+        );
+        writeln!(
+            csv,
+            "{},,,opening_balance,,,{},",
+            self.from,
+            self.amount(self.opening_balance)
+        )
+        .unwrap();
+        let mut balance = self.opening_balance;
+        for entry in &self.entries {
+            balance += entry.value;
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                entry.posted_at.as_deref().unwrap_or_default(),
+                entry.transaction_id,
+                entry
+                    .standing_order_run
+                    .map(|run| run.to_string())
+                    .unwrap_or_default(),
+                entry.transaction_type,
+                hex::encode(entry.counterparty.0),
+                self.amount(entry.value),
+                self.amount(balance),
+                csv_field(entry.memo.as_deref().unwrap_or_default())
+            )
+            .unwrap();
+        }
+        writeln!(
+            csv,
+            "{},,,closing_balance,,,{},",
+            self.to,
+            self.amount(self.closing_balance)
+        )
+        .unwrap();
+        csv
+    }
+
+    // OFX 2.2 bank statement. OFX has no opening balance so only the closing
+    // balance is reported as the ledger balance.
+    pub fn to_ofx(&self) -> String {
+        let mut ofx = String::new();
+        writeln!(ofx, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(
+            ofx,
+            r#"<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>"#
+        )
+        .unwrap();
+        writeln!(ofx, "<OFX><BANKMSGSRSV1><STMTTRNRS>").unwrap();
+        writeln!(
+            ofx,
+            "<TRNUID>0</TRNUID><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>"
+        )
+        .unwrap();
+        writeln!(
+            ofx,
+            "<STMTRS><CURDEF>{}</CURDEF>",
+            self.currency.to_string().to_uppercase()
+        )
+        .unwrap();
+        writeln!(
+            ofx,
+            "<BANKACCTFROM><BANKID>stable</BANKID><ACCTID>{}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>",
+            hex::encode(self.address.0)
+        )
+        .unwrap();
+        writeln!(
+            ofx,
+            "<BANKTRANLIST><DTSTART>{}</DTSTART><DTEND>{}</DTEND>",
+            ofx_date(&self.from),
+            ofx_date(&self.to)
+        )
+        .unwrap();
+        for entry in &self.entries {
+            writeln!(
+                ofx,
+                "<STMTTRN><TRNTYPE>{}</TRNTYPE><DTPOSTED>{}</DTPOSTED><TRNAMT>{}</TRNAMT><FITID>{}</FITID><NAME>{}</NAME><MEMO>{}</MEMO></STMTTRN>",
+                if entry.value < 0 { "DEBIT" } else { "CREDIT" },
+                // DTPOSTED is required so unknown times fall back to the
+                // start of the statement
+                ofx_date(entry.posted_at.as_deref().unwrap_or(&self.from)),
+                self.amount(entry.value),
+                entry.ledger_id,
+                entry.transaction_type,
+                ofx_text(&match &entry.memo {
+                    Some(memo) => format!("{} {}", hex::encode(entry.counterparty.0), memo),
+                    None => hex::encode(entry.counterparty.0),
+                })
+            )
+            .unwrap();
+        }
+        writeln!(ofx, "</BANKTRANLIST>").unwrap();
+        writeln!(
+            ofx,
+            "<LEDGERBAL><BALAMT>{}</BALAMT><DTASOF>{}</DTASOF></LEDGERBAL>",
+            self.amount(self.closing_balance),
+            ofx_date(&self.to)
+        )
+        .unwrap();
+        writeln!(ofx, "</STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>").unwrap();
+        ofx
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn ofx_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Converts 2024-10-01 or 2024-10-01T12:00:00Z to 20241001 or 20241001120000
+fn ofx_date(date: &str) -> String {
+    date.chars().filter(char::is_ascii_digit).collect()
+}
@@ -1,14 +1,21 @@
+use crate::{
+    constants::MAX_CURRENCY_CODE_LENGTH,
+    error::{Error, Result},
+};
 use borsh::{BorshDeserialize, BorshSerialize};
-use serde::Deserialize;
-use std::str::FromStr;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{fmt, str::FromStr};
 
 #[cfg(test)]
 use k256::ecdsa::SigningKey;
 
+// Currency codes such as "usd" or "eur". Which currencies exist and whether
+// they are enabled is stored in the currencies table so they can be added
+// without a new release.
 #[derive(
     Hash,
     BorshSerialize,
-    BorshDeserialize,
     PartialEq,
     Clone,
     Debug,
@@ -16,56 +23,110 @@ use k256::ecdsa::SigningKey;
     sqlx::Type,
     Eq,
     Deserialize,
+    Serialize,
 )]
-#[sqlx(type_name = "currency", rename_all = "lowercase")]
-pub enum Currency {
-    Usd,
-}
-#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+#[sqlx(transparent)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency(String);
+
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize)]
 pub enum Address {
     Bitcoin(String),
     Stable(crate::Address),
+    // Resolved to a Stable address from the name registry when the transaction runs
+    Name(String),
 }
 
 impl FromStr for Currency {
-    type Err = ();
+    type Err = Error;
 
-    fn from_str(input: &str) -> Result<Self, Self::Err> {
-        match input {
-            "usd" => Ok(Self::Usd),
-            _ => Err(()),
+    fn from_str(input: &str) -> Result<Self> {
+        let code = input.to_lowercase();
+        if code.is_empty()
+            || code.len() > MAX_CURRENCY_CODE_LENGTH
+            || !code
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        {
+            return Err(Error::Error(format!("Invalid currency code {}", input)));
         }
+        Ok(Self(code))
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = Error;
+
+    fn try_from(code: String) -> Result<Self> {
+        Self::from_str(&code)
+    }
+}
+
+// Decoded codes are validated like any other so invalid currencies can't be
+// smuggled in through the wire format
+impl BorshDeserialize for Currency {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        Self::from_str(&String::deserialize_reader(reader)?).map_err(|error| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, error.to_string())
+        })
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.0
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct Transfer {
     pub currency: Currency,
     pub to: Address,
     pub value: i64,
+    pub memo: Option<String>,
+    pub invoice_id: Option<i64>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct BatchTransfer {
+    pub transfers: Vec<Transfer>,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct CreateCheck {
     pub signer: crate::Address,
     pub currency: Currency,
     pub value: i64,
+    pub expires_at: Option<i64>,
+    pub memo: Option<String>,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct CashCheck {
     pub transaction_id: i64,
+    pub value: i64,
+    #[serde(serialize_with = "serialize_hex")]
     pub signature: [u8; 65],
 }
 impl CashCheck {
     #[cfg(test)]
     pub fn sign(
         transaction_id: i64,
+        value: i64,
         recipient_address: crate::Address,
+        cashed: i64,
         signing_key: &SigningKey,
     ) -> Self {
         let (signature, recovery_id) = signing_key
-            .sign_recoverable(&borsh::to_vec(&(transaction_id, &recipient_address)).unwrap())
+            .sign_recoverable(
+                &borsh::to_vec(&(transaction_id, value, &recipient_address, cashed)).unwrap(),
+            )
             .unwrap();
         let signature_bytes: [u8; 65] = [signature.to_bytes().as_slice(), &[recovery_id.to_byte()]]
             .concat()
@@ -73,14 +134,256 @@ impl CashCheck {
             .unwrap();
         Self {
             transaction_id: transaction_id,
+            value,
+            signature: signature_bytes,
+        }
+    }
+
+    // The signature covers the value and how much of the check had already
+    // been cashed so it can't be replayed to cash the check again
+    pub fn signer(
+        &self,
+        recipient_address: &crate::Address,
+        cashed: i64,
+    ) -> Result<crate::Address> {
+        Ok(recover(
+            &borsh::to_vec(&(self.transaction_id, self.value, recipient_address, cashed))?,
+            &self.signature,
+        )?
+        .into())
+    }
+}
+
+fn serialize_hex<S: Serializer>(
+    bytes: impl AsRef<[u8]>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+fn serialize_hex_list<S: Serializer>(
+    list: &[impl AsRef<[u8]>],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(list.iter().map(hex::encode))
+}
+
+pub fn recover(message: &[u8], signature: &[u8; 65]) -> Result<VerifyingKey> {
+    let recovery_id = RecoveryId::from_byte(signature[64]).ok_or(Error::InvalidSignatureError(
+        "Invalid recovery id".to_string(),
+    ))?;
+    Ok(VerifyingKey::recover_from_msg(
+        message,
+        &Signature::from_slice(&signature[0..64])?,
+        recovery_id,
+    )?)
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct MultiSigPolicy {
+    pub threshold: u8,
+    #[serde(serialize_with = "serialize_hex_list")]
+    pub public_keys: Vec<[u8; 33]>,
+}
+
+impl MultiSigPolicy {
+    pub fn verify(&self, message: &[u8], signatures: &[[u8; 65]]) -> Result<()> {
+        if self.threshold == 0 || self.threshold as usize > self.public_keys.len() {
+            return Err(Error::InvalidSignatureError(
+                "Multisig threshold must be between 1 and the number of public keys".to_string(),
+            ));
+        }
+        let mut signers: Vec<[u8; 33]> = vec![];
+        for signature in signatures {
+            let public_key: [u8; 33] = recover(message, signature)?
+                .to_sec1_bytes()
+                .as_ref()
+                .try_into()?;
+            if self.public_keys.contains(&public_key) && !signers.contains(&public_key) {
+                signers.push(public_key);
+            }
+        }
+        if signers.len() < self.threshold as usize {
+            return Err(Error::InvalidSignatureError(format!(
+                "Expected {} signatures from the multisig policy but found {}",
+                self.threshold,
+                signers.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct VoidCheck {
+    pub transaction_id: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct LockTransfer {
+    pub to: crate::Address,
+    pub currency: Currency,
+    pub value: i64,
+    #[serde(serialize_with = "serialize_hex")]
+    pub hashlock: [u8; 32],
+    pub timeout: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct ClaimLock {
+    pub transaction_id: i64,
+    #[serde(serialize_with = "serialize_hex")]
+    pub preimage: [u8; 32],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct RefundLock {
+    pub transaction_id: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct CreateStandingOrder {
+    pub to: crate::Address,
+    pub currency: Currency,
+    pub value: i64,
+    pub interval: i64,
+    pub max_occurrences: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct CancelStandingOrder {
+    pub transaction_id: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct Approve {
+    pub spender: crate::Address,
+    pub currency: Currency,
+    pub limit: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct TransferFrom {
+    pub owner: crate::Address,
+    pub to: crate::Address,
+    pub currency: Currency,
+    pub value: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct RotateKey {
+    #[serde(serialize_with = "serialize_hex")]
+    pub public_key: [u8; 33],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct SetRecoveryPolicy {
+    pub guardians: Vec<crate::Address>,
+    pub threshold: u8,
+    pub delay: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct InitiateRecovery {
+    pub account: crate::Address,
+    #[serde(serialize_with = "serialize_hex")]
+    pub public_key: [u8; 33],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct ApproveRecovery {
+    pub transaction_id: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct CancelRecovery {
+    pub transaction_id: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct FinalizeRecovery {
+    pub transaction_id: i64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct CreateInvoice {
+    pub currency: Currency,
+    pub value: i64,
+    pub memo: Option<String>,
+    pub expires_at: Option<i64>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct AuthorizeSessionKey {
+    #[serde(serialize_with = "serialize_hex")]
+    pub public_key: [u8; 33],
+    pub currency: Currency,
+    pub limit: i64,
+    pub expires_at: i64,
+    pub recipients: Vec<crate::Address>,
+    #[serde(serialize_with = "serialize_hex")]
+    pub signature: [u8; 65],
+}
+
+impl AuthorizeSessionKey {
+    #[cfg(test)]
+    pub fn sign(
+        account: crate::Address,
+        currency: Currency,
+        limit: i64,
+        expires_at: i64,
+        recipients: Vec<crate::Address>,
+        session_key: &SigningKey,
+    ) -> Self {
+        let (signature, recovery_id) = session_key
+            .sign_recoverable(
+                &borsh::to_vec(&(&account, &currency, limit, expires_at, &recipients)).unwrap(),
+            )
+            .unwrap();
+        let signature_bytes: [u8; 65] = [signature.to_bytes().as_slice(), &[recovery_id.to_byte()]]
+            .concat()
+            .try_into()
+            .unwrap();
+        Self {
+            public_key: VerifyingKey::from(session_key)
+                .to_sec1_bytes()
+                .as_ref()
+                .try_into()
+                .unwrap(),
+            currency,
+            limit,
+            expires_at,
+            recipients,
             signature: signature_bytes,
         }
     }
+
+    // The session key signs the account and terms it's being authorized with
+    // so an account can't claim a key it doesn't hold
+    pub fn signer(&self, account: &crate::Address) -> Result<crate::Address> {
+        Ok(recover(
+            &borsh::to_vec(&(
+                account,
+                &self.currency,
+                self.limit,
+                self.expires_at,
+                &self.recipients,
+            ))?,
+            &self.signature,
+        )?
+        .into())
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct RegisterName {
+    pub name: String,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct ClaimUtxo {
     pub currency: Currency,
+    #[serde(serialize_with = "serialize_hex")]
     pub transaction_id: [u8; 32],
     pub vout: i32,
 }